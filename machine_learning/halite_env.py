@@ -0,0 +1,68 @@
+import subprocess
+import json
+import numpy as np
+
+
+class HaliteEnv:
+    """
+    Gym-style wrapper around `my_bot gym`, which plays games in the bot's built-in engine.
+
+    seats are as for the gym subcommand, e.g. ['assisted', 'self'] to steer single ships
+    of our bot against itself, or ['agent', 'baseline:greedy'].
+    """
+
+    def __init__(self, seats=('assisted', 'self'), bot='../target/release/my_bot', config=None):
+        args = [bot]
+        if config is not None:
+            args += ['-c', config]
+        args += ['gym'] + list(seats)
+        self.proc = subprocess.Popen(args, stdin=subprocess.PIPE, stdout=subprocess.PIPE, universal_newlines=True)
+
+    def _request(self, request):
+        self.proc.stdin.write(json.dumps(request) + '\n')
+        self.proc.stdin.flush()
+        response = json.loads(self.proc.stdout.readline())
+        if 'error' in response:
+            raise RuntimeError(response['error'])
+        return response
+
+    def reset(self, size=32, players=2, seed=None):
+        """Start a game and return the observations of all players."""
+        request = {'size': size, 'players': players}
+        if seed is not None:
+            request['seed'] = seed
+        return self._request({'reset': request})['observations']
+
+    def step(self, commands):
+        """
+        Play a turn with one list of orders per player, e.g. [['m 3 n', 'g'], []].
+
+        Returns observations, rewards and done.
+        """
+        res = self._request({'step': commands})
+        return res['observations'], np.array(res['rewards']), res['done']
+
+    def close(self):
+        self.proc.stdin.close()
+        self.proc.wait()
+
+
+def halite_map(observation):
+    return np.array(observation['halite'])
+
+
+if __name__ == '__main__':
+    # steer our first ship at random and compare its cargo with the rest of the fleet
+    env = HaliteEnv()
+    obs = env.reset(size=32, players=2, seed=1)
+    done = False
+    total = np.zeros(2)
+    while not done:
+        mine = [s for s in obs[0]['ships'] if s['owner'] == 0]
+        commands = [[], []]
+        if mine:
+            commands[0] = ['m {} {}'.format(mine[0]['id'], np.random.choice(list('nsewo')))]
+        obs, rewards, done = env.step(commands)
+        total += rewards
+    print('rewards:', total)
+    env.close()
@@ -1,18 +1,23 @@
+use bt_tasks;
 use commander::Commander;
+use config::ControllerKind;
+use events::GameEvent;
+use hlt::direction::Direction;
 use hlt::log::Log;
 use hlt::ShipId;
-use ship_ai::ShipAi;
+use pda::Outcome;
+use ship_ai::{ShipAi, ShipRole};
 use std::cell::{RefCell, RefMut};
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 use GameState;
 
 pub struct AiManager {
     commander: RefCell<Commander>,
     pub ships: HashMap<ShipId, RefCell<ShipAi>>,
-
-    new_ships: HashSet<ShipId>,
-    lost_ships: HashSet<ShipId>,
-    prev_ships: HashSet<ShipId>,
+    /// Ships steered from outside this turn, e.g. by a learning agent in a `gym::Env`. Their
+    /// AI keeps its state but does not think, and the other ships plan around their moves.
+    /// Ids of ships we do not have are ignored.
+    pub steered: HashMap<ShipId, Direction>,
 }
 
 impl AiManager {
@@ -20,33 +25,79 @@ impl AiManager {
         AiManager {
             commander: RefCell::new(Commander::new()),
             ships: HashMap::new(),
-            new_ships: HashSet::new(),
-            lost_ships: HashSet::new(),
-            prev_ships: HashSet::new(),
+            steered: HashMap::new(),
         }
     }
 
     pub fn think(&mut self, world: &mut GameState) {
-        let state_ships: HashSet<_> = world.my_ships().collect();
-        self.new_ships.extend(&state_ships - &self.prev_ships);
-        self.lost_ships.extend(&self.prev_ships - &state_ships);
-        self.prev_ships = &self.prev_ships & &state_ships;
+        let my_id = world.game.my_id;
+        let mut removed = vec![];
+        for event in &world.events {
+            match *event {
+                GameEvent::ShipSpawned { id, owner, .. } if owner == my_id => {
+                    let role = self.commander().ship_role();
+                    let controllers = &world.config.ships.controllers;
+                    let kind = match role {
+                        ShipRole::Opening => controllers.opening,
+                        ShipRole::Regular => controllers.regular,
+                    };
+                    Log::log(&format!("{:?} joins as {:?} ship ({:?})", id, role, kind));
+                    let ai = match kind {
+                        ControllerKind::PushdownAutomaton => ShipAi::new(id),
+                        ControllerKind::BehaviorTree => {
+                            ShipAi::with_tree(id, bt_tasks::collector(id))
+                        }
+                    };
+                    self.ships.insert(id, RefCell::new(ai));
+                }
+                GameEvent::ShipDestroyed {
+                    id,
+                    owner,
+                    location,
+                    ref collided_with,
+                    ..
+                } if owner == my_id => {
+                    Log::log(&format!(
+                        "lost {:?} at {:?} in collision with {:?}",
+                        id, location, collided_with
+                    ));
+                    removed.push((id, Outcome::Failed));
+                }
+                GameEvent::DropoffCreated {
+                    builder: Some(id),
+                    owner,
+                    ..
+                } if owner == my_id => {
+                    removed.push((id, Outcome::Confirmed));
+                }
+                _ => {}
+            }
+        }
 
-        for id in self.lost_ships.drain() {
-            self.ships.remove(&id);
+        for (id, outcome) in removed {
+            if self.ships.contains_key(&id) {
+                self.ship(id).resolve(outcome, self, world);
+                self.ships.remove(&id);
+            }
         }
 
-        for id in self.new_ships.drain() {
-            self.prev_ships.insert(id);
-            self.ships.insert(id, RefCell::new(ShipAi::new(id)));
+        let n_surprises = world.expectations.discrepancies().len();
+        if n_surprises > 0 {
+            Log::log(&format!(
+                "{} discrepancies with last turn's expectations",
+                n_surprises
+            ));
         }
 
         Log::log(&format!("commanding {} ships", self.ships.len()));
 
         self.commander.borrow_mut().think(self, world);
 
-        for ship in self.ships.values() {
-            ship.borrow_mut().think(self, world)
+        for (&id, ship) in &self.ships {
+            match self.steered.get(&id) {
+                Some(&d) => steer(id, d, world),
+                None => ship.borrow_mut().think(self, world),
+            }
         }
 
         world.gns.solve_moves();
@@ -61,3 +112,25 @@ impl AiManager {
         self.ships[&id].borrow_mut()
     }
 }
+
+/// Plan the move of a steered ship like a stuck ship's: every other option is forbidden, so
+/// nothing else can take its place.
+fn steer(id: ShipId, d: Direction, world: &mut GameState) {
+    let pos = world.get_ship(id).position;
+    let cost = |option| {
+        if option == d {
+            0
+        } else {
+            i32::MAX
+        }
+    };
+    world.gns.plan_move(
+        id,
+        pos,
+        cost(Direction::Still),
+        cost(Direction::North),
+        cost(Direction::South),
+        cost(Direction::East),
+        cost(Direction::West),
+    );
+}
@@ -0,0 +1,192 @@
+use hlt::command::Command;
+use hlt::direction::Direction;
+use hlt::error::ProtocolError;
+use hlt::game::Game;
+use hlt::input::Input;
+use hlt::log::Log;
+use hlt::navi::Navi;
+use hlt::position::Position;
+use hlt::ship::Ship;
+use rng::Rng;
+use std::io::Write;
+
+/// Simple reference strategies, so new behaviour gets tested against other styles than our
+/// own. They only use the `hlt` types and ignore the config.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Baseline {
+    /// Moves every ship in a random direction and spawns while it can.
+    Random,
+    /// The classic starter bot: harvest until the cell is poor, wander randomly, and bring
+    /// full ships home with `Navi::naive_navigate`.
+    Starter,
+    /// Always moves to the richest neighbouring cell and returns when nearly full.
+    Greedy,
+    /// Parks its ships on the opponents' shipyards.
+    Griefer,
+}
+
+impl Baseline {
+    pub const ALL: [Baseline; 4] = [
+        Baseline::Random,
+        Baseline::Starter,
+        Baseline::Greedy,
+        Baseline::Griefer,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Baseline::Random => "random",
+            Baseline::Starter => "starter",
+            Baseline::Greedy => "greedy",
+            Baseline::Griefer => "griefer",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Baseline::ALL.iter().cloned().find(|b| b.name() == name)
+    }
+
+    fn commands(self, game: &Game, navi: &mut Navi, rng: &mut Rng) -> Vec<Command> {
+        let me = &game.players[game.my_id.0];
+        let mut commands = vec![];
+
+        for id in &me.ship_ids {
+            let ship = &game.ships[id];
+            let direction = match self {
+                Baseline::Random => random_move(ship, navi, rng),
+                Baseline::Starter => starter_move(game, ship, navi, rng),
+                Baseline::Greedy => greedy_move(game, ship, navi, rng),
+                Baseline::Griefer => griefer_move(game, ship, navi),
+            };
+            commands.push(Command::move_ship(ship.id, direction));
+        }
+
+        let c = &game.constants;
+        let wants_ship = match self {
+            Baseline::Random | Baseline::Starter => game.turn_number <= c.max_turns / 2,
+            Baseline::Greedy => game.turn_number <= c.max_turns * 3 / 5,
+            Baseline::Griefer => me.ship_ids.len() < 2 * (game.players.len() - 1),
+        };
+        if wants_ship && me.halite >= c.ship_cost && navi.is_safe(&me.shipyard.position) {
+            commands.push(me.shipyard.spawn());
+        }
+
+        commands
+    }
+}
+
+fn random_move(ship: &Ship, navi: &mut Navi, rng: &mut Rng) -> Direction {
+    let options = Direction::get_all_options();
+    let direction = options[rng.below(options.len())];
+    let target = ship.position.directional_offset(direction);
+    if direction == Direction::Still || navi.is_safe(&target) {
+        navi.mark_unsafe(&target, ship.id);
+        direction
+    } else {
+        Direction::Still
+    }
+}
+
+/// Our closest shipyard or dropoff.
+fn nearest_structure(game: &Game, from: &Position) -> Position {
+    let me = &game.players[game.my_id.0];
+    me.dropoff_ids
+        .iter()
+        .map(|id| game.dropoffs[id].position)
+        .chain(Some(me.shipyard.position))
+        .min_by_key(|p| game.map.calculate_distance(from, p))
+        .unwrap()
+}
+
+fn starter_move(game: &Game, ship: &Ship, navi: &mut Navi, rng: &mut Rng) -> Direction {
+    if ship.is_full() {
+        let home = nearest_structure(game, &ship.position);
+        return navi.naive_navigate(ship, &home);
+    }
+
+    let cell = game.map.at_position(&ship.position);
+    if cell.halite < game.constants.max_halite / 10 {
+        let cardinals = Direction::get_all_cardinals();
+        let direction = cardinals[rng.below(cardinals.len())];
+        return navi.naive_navigate(ship, &ship.position.directional_offset(direction));
+    }
+
+    Direction::Still
+}
+
+fn greedy_move(game: &Game, ship: &Ship, navi: &mut Navi, rng: &mut Rng) -> Direction {
+    let c = &game.constants;
+    let here = game.map.at_position(&ship.position).halite;
+    if ship.halite < here / c.move_cost_ratio {
+        return Direction::Still;
+    }
+
+    if ship.halite >= c.max_halite * 9 / 10 {
+        let home = nearest_structure(game, &ship.position);
+        return navi.naive_navigate(ship, &home);
+    }
+
+    let best = Direction::get_all_cardinals()
+        .into_iter()
+        .map(|d| (d, ship.position.directional_offset(d)))
+        .filter(|(_, p)| navi.is_safe(p))
+        .max_by_key(|(_, p)| game.map.at_position(p).halite);
+
+    // a cell is worth mining until it is down to a tenth, like in the starter bot
+    let depleted = c.max_halite / 10;
+    match best {
+        // moving costs a tenth of the halite here and the first turn on the new cell mines
+        // a quarter of its halite
+        Some((d, p))
+            if game.map.at_position(&p).halite > 2 * here + 40
+                || here < depleted && game.map.at_position(&p).halite >= depleted =>
+        {
+            navi.mark_unsafe(&p, ship.id);
+            d
+        }
+        // nothing worth mining next door, so wander off like the starter bot
+        _ if here < depleted => {
+            let cardinals = Direction::get_all_cardinals();
+            let direction = cardinals[rng.below(cardinals.len())];
+            navi.naive_navigate(ship, &ship.position.directional_offset(direction))
+        }
+        _ => Direction::Still,
+    }
+}
+
+fn griefer_move(game: &Game, ship: &Ship, navi: &mut Navi) -> Direction {
+    // ships take turns camping on each opponent's shipyard
+    let opponents: Vec<_> = game.players.iter().filter(|p| p.id != game.my_id).collect();
+    let target = opponents[ship.id.0 % opponents.len()].shipyard.position;
+
+    if ship.position == target || ship.halite < game.map.at_position(&ship.position).halite / 10 {
+        return Direction::Still;
+    }
+    navi.naive_navigate(ship, &target)
+}
+
+/// Play one game with a baseline strategy.
+pub fn run(
+    baseline: Baseline,
+    input: Input,
+    output: Box<dyn Write + Send>,
+) -> Result<(), ProtocolError> {
+    let mut game = Game::new(input, output)?;
+    game.ready(&format!("baseline-{}", baseline.name()))?;
+    Log::log(&format!("playing the {} baseline", baseline.name()));
+
+    let seed = game.constants.game_seed.unwrap_or(0);
+    let mut rng = Rng::new(seed ^ game.my_id.0 as u64);
+    let mut navi = Navi::new(game.map.width, game.map.height);
+
+    loop {
+        match game.update_frame() {
+            Ok(()) => {}
+            Err(ref e) if e.is_eof() => return Ok(()),
+            Err(e) => return Err(e),
+        }
+        navi.update_frame(&game);
+        let commands = baseline.commands(&game, &mut navi, &mut rng);
+        game.end_turn(&commands)?;
+    }
+}
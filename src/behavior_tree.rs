@@ -0,0 +1,482 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+type NodePtr<E> = Box<dyn BtNode<E>>;
+
+pub trait BtNode<E> {
+    fn tick(&mut self, env: &mut E) -> BtState;
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum BtState {
+    NotStarted,
+    Running,
+    Success,
+    Failure,
+}
+
+/// Values shared between the nodes of a tree.
+#[derive(Default)]
+pub struct Blackboard {
+    values: HashMap<&'static str, Box<dyn Any>>,
+}
+
+pub type SharedBlackboard = Rc<RefCell<Blackboard>>;
+
+impl Blackboard {
+    pub fn shared() -> SharedBlackboard {
+        Rc::new(RefCell::new(Blackboard::default()))
+    }
+
+    pub fn get<T: Any + Clone>(&self, key: &str) -> Option<T> {
+        self.values
+            .get(key)
+            .and_then(|value| value.downcast_ref::<T>())
+            .cloned()
+    }
+
+    pub fn set<T: Any>(&mut self, key: &'static str, value: T) {
+        self.values.insert(key, Box::new(value));
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        self.values.remove(key);
+    }
+}
+
+pub fn lambda<E, F: FnMut(&mut E) -> BtState>(func: F) -> Box<impl BtNode<E>> {
+    Box::new(Lambda::new(func))
+}
+
+pub fn condition<E, P: FnMut(&mut E) -> bool>(mut func: P) -> Box<impl BtNode<E>> {
+    lambda(move |e| {
+        if func(e) {
+            BtState::Success
+        } else {
+            BtState::Failure
+        }
+    })
+}
+
+pub fn continuous<E>(mut child: NodePtr<E>) -> Box<impl BtNode<E>> {
+    lambda(move |state| {
+        while child.tick(state) != BtState::Running {}
+        BtState::Running
+    })
+}
+
+pub fn sequence<E>(children: Vec<NodePtr<E>>) -> Box<impl BtNode<E>> {
+    Box::new(Sequence::new(children))
+}
+
+pub fn select<E>(children: Vec<NodePtr<E>>) -> Box<impl BtNode<E>> {
+    Box::new(Selector::new(children))
+}
+
+/// Tick all children every turn. Succeeds as soon as `required` children succeeded, and fails
+/// as soon as that is no longer possible.
+pub fn parallel<E>(children: Vec<NodePtr<E>>, required: usize) -> Box<impl BtNode<E>> {
+    Box::new(Parallel::new(children, required))
+}
+
+/// Tick the child and pass on its state, unless `func` says to stop, in which case the node
+/// fails without ticking the child.
+pub fn interrupt<E, P: FnMut(&mut E) -> bool>(child: NodePtr<E>, func: P) -> Box<impl BtNode<E>> {
+    Box::new(Interrupt::new(child, func))
+}
+
+#[cfg_attr(not(test), allow(dead_code))]
+pub fn invert<E>(mut child: NodePtr<E>) -> Box<impl BtNode<E>> {
+    lambda(move |e| match child.tick(e) {
+        BtState::Success => BtState::Failure,
+        BtState::Failure => BtState::Success,
+        s => s,
+    })
+}
+
+#[cfg_attr(not(test), allow(dead_code))]
+pub fn succeed<E>(mut child: NodePtr<E>) -> Box<impl BtNode<E>> {
+    lambda(move |e| match child.tick(e) {
+        BtState::Failure => BtState::Success,
+        s => s,
+    })
+}
+
+/// Run the child to success `n` times in a row. Fails as soon as the child fails.
+#[cfg_attr(not(test), allow(dead_code))]
+pub fn repeat<E>(n: usize, mut child: NodePtr<E>) -> Box<impl BtNode<E>> {
+    let mut count = 0;
+    lambda(move |e| match child.tick(e) {
+        BtState::Success => {
+            count += 1;
+            if count >= n {
+                count = 0;
+                BtState::Success
+            } else {
+                BtState::Running
+            }
+        }
+        BtState::Failure => {
+            count = 0;
+            BtState::Failure
+        }
+        s => s,
+    })
+}
+
+/// Fail if `func` fails, otherwise wait a turn for its effect and then succeed.
+#[cfg_attr(not(test), allow(dead_code))]
+pub fn run_or_fail<E, P: FnMut(&mut E) -> bool>(mut func: P) -> Box<impl BtNode<E>> {
+    let mut state = BtState::NotStarted;
+    lambda(move |e| match state {
+        BtState::Running => {
+            state = BtState::NotStarted;
+            BtState::Success
+        }
+        BtState::NotStarted => {
+            if func(e) {
+                state = BtState::Running;
+                BtState::Running
+            } else {
+                BtState::Failure
+            }
+        }
+        BtState::Failure | BtState::Success => unreachable!(),
+    })
+}
+
+struct Lambda<F, E>
+where
+    F: FnMut(&mut E) -> BtState,
+{
+    func: F,
+    _e: PhantomData<E>,
+}
+
+impl<F, E> Lambda<F, E>
+where
+    F: FnMut(&mut E) -> BtState,
+{
+    fn new(func: F) -> Lambda<F, E> {
+        Lambda {
+            func,
+            _e: PhantomData,
+        }
+    }
+}
+
+impl<F, E> BtNode<E> for Lambda<F, E>
+where
+    F: FnMut(&mut E) -> BtState,
+{
+    fn tick(&mut self, env: &mut E) -> BtState {
+        (self.func)(env)
+    }
+}
+
+struct Sequence<E> {
+    children: Vec<NodePtr<E>>,
+    current_child: usize,
+}
+
+impl<E> Sequence<E> {
+    fn new(children: Vec<NodePtr<E>>) -> Sequence<E> {
+        Sequence {
+            children,
+            current_child: 0,
+        }
+    }
+}
+
+impl<E> BtNode<E> for Sequence<E> {
+    fn tick(&mut self, env: &mut E) -> BtState {
+        loop {
+            let ret = self.children[self.current_child].tick(env);
+
+            match ret {
+                BtState::Running => return BtState::Running,
+                BtState::Failure => {
+                    self.current_child = 0;
+                    return BtState::Failure;
+                }
+                BtState::Success => {
+                    self.current_child += 1;
+                    if self.current_child == self.children.len() {
+                        self.current_child = 0;
+                        return BtState::Success;
+                    }
+                }
+                BtState::NotStarted => panic!("Child tick returned NotStarted"),
+            }
+        }
+    }
+}
+
+struct Selector<E> {
+    children: Vec<NodePtr<E>>,
+    current_child: usize,
+}
+
+impl<E> Selector<E> {
+    fn new(children: Vec<NodePtr<E>>) -> Selector<E> {
+        Selector {
+            children,
+            current_child: 0,
+        }
+    }
+}
+
+impl<E> BtNode<E> for Selector<E> {
+    fn tick(&mut self, env: &mut E) -> BtState {
+        loop {
+            let ret = self.children[self.current_child].tick(env);
+
+            match ret {
+                BtState::Running => return BtState::Running,
+                BtState::Success => {
+                    self.current_child = 0;
+                    return BtState::Success;
+                }
+                BtState::Failure => {
+                    self.current_child += 1;
+                    if self.current_child == self.children.len() {
+                        self.current_child = 0;
+                        return BtState::Failure;
+                    }
+                }
+                BtState::NotStarted => panic!("Child tick returned NotStarted"),
+            }
+        }
+    }
+}
+
+struct Parallel<E> {
+    children: Vec<NodePtr<E>>,
+    results: Vec<BtState>,
+    required: usize,
+}
+
+impl<E> Parallel<E> {
+    fn new(children: Vec<NodePtr<E>>, required: usize) -> Parallel<E> {
+        Parallel {
+            results: vec![BtState::NotStarted; children.len()],
+            children,
+            required,
+        }
+    }
+}
+
+impl<E> BtNode<E> for Parallel<E> {
+    fn tick(&mut self, env: &mut E) -> BtState {
+        for (child, result) in self.children.iter_mut().zip(&mut self.results) {
+            match *result {
+                BtState::Success | BtState::Failure => {}
+                _ => *result = child.tick(env),
+            }
+        }
+
+        let n_success = self
+            .results
+            .iter()
+            .filter(|&&r| r == BtState::Success)
+            .count();
+        let n_failure = self
+            .results
+            .iter()
+            .filter(|&&r| r == BtState::Failure)
+            .count();
+
+        let state = if n_success >= self.required {
+            BtState::Success
+        } else if n_failure > self.children.len() - self.required {
+            BtState::Failure
+        } else {
+            return BtState::Running;
+        };
+
+        for result in &mut self.results {
+            *result = BtState::NotStarted;
+        }
+        state
+    }
+}
+
+struct Interrupt<E, P>
+where
+    P: FnMut(&mut E) -> bool,
+{
+    child: NodePtr<E>,
+    predicate: P,
+}
+
+impl<E, P> Interrupt<E, P>
+where
+    P: FnMut(&mut E) -> bool,
+{
+    fn new(child: NodePtr<E>, predicate: P) -> Self {
+        Interrupt { child, predicate }
+    }
+}
+
+impl<E, P> BtNode<E> for Interrupt<E, P>
+where
+    P: FnMut(&mut E) -> bool,
+{
+    fn tick(&mut self, env: &mut E) -> BtState {
+        if (self.predicate)(env) {
+            BtState::Failure
+        } else {
+            self.child.tick(env)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A leaf that counts its ticks and returns the given states in turn.
+    fn script(states: Vec<BtState>) -> Box<impl BtNode<Vec<usize>>> {
+        let mut i = 0;
+        lambda(move |ticks: &mut Vec<usize>| {
+            ticks.push(i);
+            let state = states[i % states.len()];
+            i += 1;
+            state
+        })
+    }
+
+    fn tick<N: BtNode<Vec<usize>> + ?Sized>(node: &mut N) -> BtState {
+        node.tick(&mut vec![])
+    }
+
+    #[test]
+    fn sequence_stops_at_the_first_failure() {
+        let mut env = vec![];
+        let mut node = sequence(vec![
+            condition(|_: &mut Vec<usize>| true),
+            script(vec![BtState::Running, BtState::Failure]),
+            condition(|_: &mut Vec<usize>| panic!("ticked after a failure")),
+        ]);
+        assert_eq!(node.tick(&mut env), BtState::Running);
+        assert_eq!(node.tick(&mut env), BtState::Failure);
+        assert_eq!(env, vec![0, 1]);
+    }
+
+    #[test]
+    fn selector_falls_through_failures() {
+        let mut node = select(vec![
+            condition(|_: &mut Vec<usize>| false),
+            script(vec![BtState::Running, BtState::Success]),
+        ]);
+        assert_eq!(tick(&mut *node), BtState::Running);
+        assert_eq!(tick(&mut *node), BtState::Success);
+        assert_eq!(
+            tick(&mut *select(vec![condition(|_: &mut Vec<usize>| false)])),
+            BtState::Failure
+        );
+    }
+
+    #[test]
+    fn parallel_needs_the_required_successes() {
+        let mut node = parallel(
+            vec![
+                script(vec![BtState::Running, BtState::Success]),
+                script(vec![BtState::Running]),
+            ],
+            1,
+        );
+        assert_eq!(tick(&mut *node), BtState::Running);
+        assert_eq!(tick(&mut *node), BtState::Success);
+
+        let mut node = parallel(
+            vec![
+                script(vec![BtState::Failure]),
+                script(vec![BtState::Running, BtState::Success]),
+            ],
+            2,
+        );
+        assert_eq!(tick(&mut *node), BtState::Failure);
+    }
+
+    #[test]
+    fn parallel_does_not_tick_finished_children() {
+        let mut env = vec![];
+        let mut node = parallel(
+            vec![
+                script(vec![BtState::Success, BtState::Failure]),
+                script(vec![BtState::Running, BtState::Success]),
+            ],
+            2,
+        );
+        assert_eq!(node.tick(&mut env), BtState::Running);
+        assert_eq!(node.tick(&mut env), BtState::Success);
+        assert_eq!(env, vec![0, 0, 1]);
+    }
+
+    #[test]
+    fn decorators() {
+        assert_eq!(
+            tick(&mut *invert(script(vec![BtState::Success]))),
+            BtState::Failure
+        );
+        assert_eq!(
+            tick(&mut *invert(script(vec![BtState::Running]))),
+            BtState::Running
+        );
+        assert_eq!(
+            tick(&mut *succeed(script(vec![BtState::Failure]))),
+            BtState::Success
+        );
+
+        let mut node = repeat(2, script(vec![BtState::Success]));
+        assert_eq!(tick(&mut *node), BtState::Running);
+        assert_eq!(tick(&mut *node), BtState::Success);
+        assert_eq!(tick(&mut *node), BtState::Running);
+    }
+
+    #[test]
+    fn interrupt_passes_on_the_child_state() {
+        let mut env = vec![];
+        let mut stop = false;
+        let mut node = interrupt(
+            script(vec![BtState::Running, BtState::Failure]),
+            move |_: &mut Vec<usize>| {
+                let s = stop;
+                stop = true;
+                s
+            },
+        );
+        assert_eq!(node.tick(&mut env), BtState::Running);
+        assert_eq!(node.tick(&mut env), BtState::Failure);
+        assert_eq!(env, vec![0]);
+
+        let mut node = interrupt(script(vec![BtState::Failure]), |_: &mut Vec<usize>| false);
+        assert_eq!(tick(&mut *node), BtState::Failure);
+    }
+
+    #[test]
+    fn run_or_fail_waits_a_turn() {
+        let mut node = run_or_fail(|_: &mut Vec<usize>| true);
+        assert_eq!(tick(&mut *node), BtState::Running);
+        assert_eq!(tick(&mut *node), BtState::Success);
+        assert_eq!(tick(&mut *node), BtState::Running);
+        assert_eq!(
+            tick(&mut *run_or_fail(|_: &mut Vec<usize>| false)),
+            BtState::Failure
+        );
+    }
+
+    #[test]
+    fn blackboard_values() {
+        let mut bb = Blackboard::default();
+        bb.set("trips", 2usize);
+        assert_eq!(bb.get::<usize>("trips"), Some(2));
+        assert_eq!(bb.get::<i32>("trips"), None);
+        bb.remove("trips");
+        assert_eq!(bb.get::<usize>("trips"), None);
+    }
+}
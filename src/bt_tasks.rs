@@ -0,0 +1,100 @@
+use behavior_tree::{
+    condition, continuous, interrupt, lambda, parallel, select, sequence, Blackboard, BtNode,
+    BtState,
+};
+use commander::must_go_home;
+use hlt::log::Log;
+use hlt::ShipId;
+use pda::{Driver, Hook, StackOp, StateStack, StateTime};
+use ship_ai::{Collect, Deliver, GoHome, ShipAiState};
+use GameState;
+
+/// Drives the states of a single task without asking the commander for more.
+struct TaskDriver<'a> {
+    id: ShipId,
+    world: &'a mut GameState,
+}
+
+impl<'a> Driver<Box<dyn ShipAiState>> for TaskDriver<'a> {
+    fn now(&self) -> usize {
+        self.world.game.turn_number
+    }
+
+    fn step(&mut self, state: &mut Box<dyn ShipAiState>) -> StackOp<Box<dyn ShipAiState>> {
+        self.world.note_state(self.id, state.name());
+        state.step(self.id, self.world)
+    }
+
+    fn hook(&mut self, state: &mut Box<dyn ShipAiState>, hook: Hook, _time: StateTime) {
+        match hook {
+            Hook::Enter => state.on_enter(self.id, self.world),
+            Hook::Exit => state.on_exit(self.id, self.world),
+            Hook::Resume => state.on_resume(self.id, self.world),
+        }
+    }
+}
+
+/// Run a ship AI state (and the states it pushes) as a leaf of the tree. The leaf succeeds
+/// once the state is done.
+pub fn task<F>(id: ShipId, mut make: F) -> Box<impl BtNode<GameState>>
+where
+    F: FnMut() -> Box<dyn ShipAiState>,
+{
+    let mut states: Option<StateStack<Box<dyn ShipAiState>>> = None;
+    lambda(move |world: &mut GameState| {
+        let stack = states.get_or_insert_with(StateStack::default);
+        let mut driver = TaskDriver { id, world };
+        if stack.is_empty() {
+            stack.transition(StackOp::Push(make()), &mut driver);
+        }
+
+        stack.run(&mut driver);
+
+        if stack.is_empty() {
+            BtState::Success
+        } else {
+            BtState::Running
+        }
+    })
+}
+
+/// Collect and deliver until it is time to go home.
+pub fn collector(id: ShipId) -> Box<impl BtNode<GameState>> {
+    let blackboard = Blackboard::shared();
+
+    let (turns, trips) = (blackboard.clone(), blackboard.clone());
+    continuous(select(vec![
+        interrupt(
+            sequence(vec![
+                task(id, || Box::new(Collect)),
+                // count the turns of the way back next to the delivery
+                parallel(
+                    vec![
+                        task(id, || Box::new(Deliver::new())),
+                        lambda(move |_: &mut GameState| {
+                            let mut bb = turns.borrow_mut();
+                            let n = bb.get::<usize>("return_turns").unwrap_or(0) + 1;
+                            bb.set("return_turns", n);
+                            BtState::Running
+                        }),
+                    ],
+                    1,
+                ),
+                condition(move |_: &mut GameState| {
+                    let mut bb = trips.borrow_mut();
+                    let n = bb.get::<usize>("trips").unwrap_or(0) + 1;
+                    let turns = bb.get::<usize>("return_turns").unwrap_or(0);
+                    bb.set("trips", n);
+                    bb.remove("return_turns");
+                    Log::log(&format!(
+                        "{:?} completed trip {} with {} turns on the way back",
+                        id, n, turns
+                    ));
+                    true
+                }),
+            ]),
+            move |world| must_go_home(id, world),
+        ),
+        task(id, || Box::new(GoHome)),
+    ]))
+}
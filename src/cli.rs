@@ -0,0 +1,950 @@
+use ai_manager::AiManager;
+use baselines::Baseline;
+use config::{Config, GameMode};
+use engine::{play_game, GameSettings};
+use gym::{self, Env, Seat};
+use hlt::input::Input;
+use hlt::log::Log;
+use optimizer::{Candidate, Iteration, Optimizer, Params, TuningSpec};
+use rating::{RatingTable, Sprt};
+use rng::Rng;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::Path;
+use std::process::exit;
+use std::time::{Duration, Instant};
+use tournament::{default_threads, BotSpec, Tournament};
+use {run_bot, BotOptions, GameState};
+
+const USAGE: &str = "\
+Usage: my_bot [OPTIONS] [COMMAND]
+
+Commands:
+    play                  Play a game on stdin/stdout, as on the ladder (default)
+    playback FILE         Replay a transcript recorded with `play --record`
+    simulate [BOT...]     Play a game in the built-in engine
+    tournament BOT...     Play many games between bots in the built-in engine
+    bench FILE            Time the turn pipeline on a recorded transcript
+    tune --try KEY=VALUE  Compare config changes against the current config
+    tune --spec FILE      Search for the config values with the best win rate
+    ratings FILE          Print a rating table kept with `tournament --ratings`
+    gym [SEAT...]         Serve a Gym-style environment as JSON lines on stdin/stdout
+
+Options:
+    -c, --config FILE     Config file on top of the embedded config
+                          (default: config.json if it exists)
+    -s, --set KEY=VALUE   Override a config value, e.g. -s ships.return_cargo=900
+    -d, --dump FILE       Append the game state to FILE every turn
+        --quiet           Do not write log or statistics files
+        --baseline NAME   Play a built-in reference strategy instead of this bot:
+                          random, starter, greedy or griefer
+        --export DIR      Save each game's per-ship observations and moves to
+                          an npz file in DIR, as training data
+        --print-config    Print the effective config and exit
+    -h, --help            Print this help
+
+play:
+        --record FILE     Save the engine's messages to FILE
+
+simulate:
+    BOT                   `self` for this bot with the options above, `self:FILE`
+                          for this bot with config FILE, `baseline:NAME` for a
+                          reference strategy, or a shell command starting
+                          another bot (default: self self)
+        --size N          Map width and height (default: 32)
+        --seed N          Map seed (default: random)
+        --json            Print the result as JSON
+
+tournament:
+    BOT...                As for simulate. Statistics are for the first bot; the
+                          others take turns filling the remaining seats. Prefix
+                          a bot with NAME= to name it, e.g. v24=self:v24.cfg.json
+        --games N         Number of games (default: 100)
+        --size N          Map width and height (default: 32)
+        --players N       2 or 4 (default: 2)
+        --seed N          Seed for seats and maps (default: random)
+        --threads N       Games played in parallel (default: all cores)
+        --json            Print the report as JSON
+        --sprt ELO0,ELO1  Stop as soon as a sequential probability ratio test
+                          tells whether the first bot is ELO0 or ELO1 stronger
+        --alpha P         Chance of wrongly accepting ELO1 (default: 0.05)
+        --beta P          Chance of wrongly accepting ELO0 (default: 0.05)
+        --ratings FILE    Update the Elo ratings of all bots in FILE
+
+gym:
+    SEAT                  `agent` for a player whose orders come from the client,
+                          `assisted` for this bot with ships the client may steer,
+                          or a bot as for simulate. Player 0 takes the first seat,
+                          the others take turns filling the rest (default: assisted
+                          self). See README for the protocol.
+
+bench:
+        --runs N          Number of playbacks (default: 5)
+
+tune:
+        --spec FILE       Tuning spec for a cross-entropy search; see README
+        --out FILE        Where the search writes the best config, next to
+                          FILE.history.json (default: tuned.json)
+        --try KEY=VALUE   A candidate change; may be given several times
+        --games N         Games per candidate (default: 20)
+        --size N          Map width and height (default: 32)
+        --players N       2 or 4 (default: 2)
+        --seed N          Seed for seats and maps (default: random)
+        --threads N       Games played in parallel (default: all cores)
+                          With --spec, --games, --size, --players and --seed
+                          take precedence over the values in the spec.
+";
+
+enum Subcommand {
+    Play {
+        record: Option<String>,
+    },
+    Playback {
+        file: String,
+    },
+    Simulate {
+        settings: GameSettings,
+        bots: Vec<String>,
+        json: bool,
+    },
+    Tournament(TournamentArgs),
+    Bench {
+        file: String,
+        runs: usize,
+    },
+    Tune {
+        candidates: Vec<String>,
+        games: usize,
+        settings: GameSettings,
+        threads: usize,
+    },
+    Search {
+        spec: String,
+        out: String,
+        threads: usize,
+        overrides: SpecOverrides,
+    },
+    Ratings {
+        file: String,
+    },
+    Gym {
+        seats: Vec<String>,
+    },
+}
+
+struct TournamentArgs {
+    bots: Vec<String>,
+    games: usize,
+    settings: GameSettings,
+    threads: usize,
+    json: bool,
+    sprt: Option<Sprt>,
+    ratings: Option<String>,
+}
+
+/// Command line values that take precedence over those of a tuning spec.
+struct SpecOverrides {
+    games: Option<usize>,
+    size: Option<usize>,
+    players: Option<usize>,
+    seed: Option<u64>,
+}
+
+impl SpecOverrides {
+    fn apply(&self, spec: &mut TuningSpec) {
+        spec.games = self.games.unwrap_or(spec.games);
+        spec.size = self.size.unwrap_or(spec.size);
+        spec.players = self.players.unwrap_or(spec.players);
+        spec.seed = self.seed.or(spec.seed);
+    }
+}
+
+/// Contents of `FILE.history.json`: the iterations so far, and the result once the search is
+/// done.
+#[derive(Serialize)]
+struct SearchProgress<'a> {
+    best: Option<&'a Candidate>,
+    history: &'a [Iteration],
+}
+
+struct Cli {
+    opts: BotOptions,
+    print_config: bool,
+    command: Subcommand,
+}
+
+/// The arguments of a subcommand.
+struct Args {
+    args: std::vec::IntoIter<String>,
+}
+
+impl Args {
+    fn value(&mut self, flag: &str) -> Result<String, String> {
+        self.args
+            .next()
+            .ok_or_else(|| format!("{} needs a value", flag))
+    }
+
+    fn number<T: std::str::FromStr>(&mut self, flag: &str) -> Result<T, String> {
+        let value = self.value(flag)?;
+        value
+            .parse()
+            .map_err(|_| format!("{} needs a number, not '{}'", flag, value))
+    }
+}
+
+impl Iterator for Args {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        self.args.next()
+    }
+}
+
+fn parse(argv: Vec<String>) -> Result<Cli, String> {
+    // the config is embedded in the binary; a config.json next to it still takes precedence
+    let mut opts = BotOptions {
+        config_file: if Path::new("config.json").exists() {
+            Some("config.json".to_string())
+        } else {
+            None
+        },
+        ..BotOptions::default()
+    };
+    let mut print_config = false;
+    let mut command = None;
+    let mut rest = vec![];
+
+    // global options may appear before or after the subcommand
+    let mut argv = Args {
+        args: argv.into_iter(),
+    };
+    while let Some(arg) = argv.next() {
+        match arg.as_ref() {
+            "-h" | "--help" => {
+                print!("{}", USAGE);
+                exit(0);
+            }
+            "-c" | "--config" => opts.config_file = Some(argv.value(&arg)?),
+            "-s" | "--set" => opts.overrides.push(argv.value(&arg)?),
+            "-d" | "--dump" => opts.dump_file = Some(argv.value(&arg)?),
+            "--quiet" => opts.quiet = true,
+            "--export" => opts.export_dir = Some(argv.value(&arg)?),
+            "--baseline" => {
+                let name = argv.value(&arg)?;
+                match Baseline::from_name(&name) {
+                    Some(baseline) => opts.baseline = Some(baseline),
+                    None => {
+                        let names: Vec<_> = Baseline::ALL.iter().map(|b| b.name()).collect();
+                        return Err(format!(
+                            "unknown baseline '{}', try one of {}",
+                            name,
+                            names.join(", ")
+                        ));
+                    }
+                }
+            }
+            "--print-config" => print_config = true,
+            "play" | "playback" | "simulate" | "tournament" | "bench" | "tune" | "ratings"
+            | "gym"
+                if command.is_none() =>
+            {
+                command = Some(arg)
+            }
+            _ => rest.push(arg),
+        }
+    }
+
+    let mut args = Args {
+        args: rest.into_iter(),
+    };
+    let command = match command.as_ref().map(String::as_ref).unwrap_or("play") {
+        "play" => {
+            let mut record = None;
+            while let Some(arg) = args.next() {
+                match arg.as_ref() {
+                    "--record" => record = Some(args.value(&arg)?),
+                    _ => return Err(format!("unexpected argument '{}' for play", arg)),
+                }
+            }
+            Subcommand::Play { record }
+        }
+        "playback" => {
+            let file = args.next().ok_or("playback needs a transcript file")?;
+            if let Some(arg) = args.next() {
+                return Err(format!("unexpected argument '{}' for playback", arg));
+            }
+            Subcommand::Playback { file }
+        }
+        "simulate" => {
+            let mut size = 32;
+            let mut seed = None;
+            let mut json = false;
+            let mut bots = vec![];
+            while let Some(arg) = args.next() {
+                match arg.as_ref() {
+                    "--size" => size = args.number(&arg)?,
+                    "--seed" => seed = Some(args.number(&arg)?),
+                    "--json" => json = true,
+                    _ if arg.starts_with("--") => {
+                        return Err(format!("unexpected argument '{}' for simulate", arg))
+                    }
+                    _ => bots.push(arg),
+                }
+            }
+            if bots.is_empty() {
+                bots = vec!["self".to_string(), "self".to_string()];
+            }
+            let settings = GameSettings {
+                size,
+                players: bots.len(),
+                seed: seed.unwrap_or_else(|| Rng::from_time().next_u64() % 1_000_000_000),
+            };
+            settings.validate()?;
+            Subcommand::Simulate {
+                settings,
+                bots,
+                json,
+            }
+        }
+        "tournament" => {
+            let mut games = 100;
+            let mut size = 32;
+            let mut players = 2;
+            let mut seed = None;
+            let mut threads = default_threads();
+            let mut json = false;
+            let mut bots = vec![];
+            let mut sprt = None;
+            let mut alpha = 0.05;
+            let mut beta = 0.05;
+            let mut ratings = None;
+            while let Some(arg) = args.next() {
+                match arg.as_ref() {
+                    "--games" => games = args.number(&arg)?,
+                    "--size" => size = args.number(&arg)?,
+                    "--players" => players = args.number(&arg)?,
+                    "--seed" => seed = Some(args.number(&arg)?),
+                    "--threads" => threads = args.number(&arg)?,
+                    "--json" => json = true,
+                    "--sprt" => {
+                        let value = args.value(&arg)?;
+                        let bounds: Vec<f64> = value
+                            .split(',')
+                            .map(|x| x.trim().parse())
+                            .collect::<Result<_, _>>()
+                            .map_err(|_| format!("--sprt needs ELO0,ELO1, not '{}'", value))?;
+                        match bounds[..] {
+                            [elo0, elo1] if elo0 < elo1 => sprt = Some((elo0, elo1)),
+                            _ => return Err(format!("--sprt needs ELO0 < ELO1, not '{}'", value)),
+                        }
+                    }
+                    "--alpha" => alpha = args.number(&arg)?,
+                    "--beta" => beta = args.number(&arg)?,
+                    "--ratings" => ratings = Some(args.value(&arg)?),
+                    _ if arg.starts_with("--") => {
+                        return Err(format!("unexpected argument '{}' for tournament", arg))
+                    }
+                    _ => bots.push(arg),
+                }
+            }
+            if bots.len() < 2 || bots.len() > players {
+                return Err(format!(
+                    "tournament needs 2 to {} bots, not {}",
+                    players,
+                    bots.len()
+                ));
+            }
+            if games == 0 {
+                return Err("tournament needs at least one game".to_string());
+            }
+            let settings = GameSettings {
+                size,
+                players,
+                seed: seed.unwrap_or_else(|| Rng::from_time().next_u64() % 1_000_000_000),
+            };
+            settings.validate()?;
+            let in_range = |p: f64| p > 0.0 && p < 0.5;
+            if !in_range(alpha) || !in_range(beta) {
+                return Err("--alpha and --beta must be between 0 and 0.5".to_string());
+            }
+            Subcommand::Tournament(TournamentArgs {
+                bots,
+                games,
+                settings,
+                threads,
+                json,
+                sprt: sprt.map(|(elo0, elo1)| Sprt {
+                    elo0,
+                    elo1,
+                    alpha,
+                    beta,
+                }),
+                ratings,
+            })
+        }
+        "ratings" => {
+            let file = args.next().ok_or("ratings needs a rating file")?;
+            if let Some(arg) = args.next() {
+                return Err(format!("unexpected argument '{}' for ratings", arg));
+            }
+            Subcommand::Ratings { file }
+        }
+        "gym" => {
+            let mut seats = vec![];
+            for arg in args {
+                if arg.starts_with("--") {
+                    return Err(format!("unexpected argument '{}' for gym", arg));
+                }
+                seats.push(arg);
+            }
+            if seats.is_empty() {
+                seats = vec!["assisted".to_string(), "self".to_string()];
+            }
+            Subcommand::Gym { seats }
+        }
+        "bench" => {
+            let mut file = None;
+            let mut runs = 5;
+            while let Some(arg) = args.next() {
+                match arg.as_ref() {
+                    "--runs" => runs = args.number(&arg)?,
+                    _ if file.is_none() && !arg.starts_with("--") => file = Some(arg),
+                    _ => return Err(format!("unexpected argument '{}' for bench", arg)),
+                }
+            }
+            Subcommand::Bench {
+                file: file.ok_or("bench needs a transcript file")?,
+                runs,
+            }
+        }
+        "tune" => {
+            let mut candidates = vec![];
+            let mut games = None;
+            let mut size = None;
+            let mut players = None;
+            let mut seed = None;
+            let mut threads = default_threads();
+            let mut spec = None;
+            let mut out = "tuned.json".to_string();
+            while let Some(arg) = args.next() {
+                match arg.as_ref() {
+                    "--spec" => spec = Some(args.value(&arg)?),
+                    "--out" => out = args.value(&arg)?,
+                    "--try" => candidates.push(args.value(&arg)?),
+                    "--games" => games = Some(args.number(&arg)?),
+                    "--size" => size = Some(args.number(&arg)?),
+                    "--players" => players = Some(args.number(&arg)?),
+                    "--seed" => seed = Some(args.number(&arg)?),
+                    "--threads" => threads = args.number(&arg)?,
+                    _ => return Err(format!("unexpected argument '{}' for tune", arg)),
+                }
+            }
+            if let Some(spec) = spec {
+                if !candidates.is_empty() {
+                    return Err("tune takes either --spec or --try".to_string());
+                }
+                let overrides = SpecOverrides {
+                    games,
+                    size,
+                    players,
+                    seed,
+                };
+                return Ok(Cli {
+                    opts,
+                    print_config,
+                    command: Subcommand::Search {
+                        spec,
+                        out,
+                        threads,
+                        overrides,
+                    },
+                });
+            }
+            if candidates.is_empty() {
+                return Err("tune needs --spec FILE or at least one --try KEY=VALUE".to_string());
+            }
+            let games = games.unwrap_or(20);
+            let settings = GameSettings {
+                size: size.unwrap_or(32),
+                players: players.unwrap_or(2),
+                seed: seed.unwrap_or_else(|| Rng::from_time().next_u64() % 1_000_000_000),
+            };
+            settings.validate()?;
+            if games == 0 {
+                return Err("tune needs at least one game".to_string());
+            }
+            Subcommand::Tune {
+                candidates,
+                games,
+                settings,
+                threads,
+            }
+        }
+        _ => unreachable!(),
+    };
+
+    Ok(Cli {
+        opts,
+        print_config,
+        command,
+    })
+}
+
+pub fn main() {
+    let cli = match parse(env::args().skip(1).collect()) {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("Error: {}\n\n{}", e, USAGE);
+            exit(2);
+        }
+    };
+
+    let uses_config = match cli.command {
+        // these check the config of each bot they play
+        Subcommand::Simulate { .. } | Subcommand::Tournament(_) | Subcommand::Tune { .. } => false,
+        Subcommand::Ratings { .. } => false,
+        _ => cli.opts.baseline.is_none(),
+    };
+    if cli.print_config || uses_config {
+        let cfg_file = cli.opts.config_file.as_ref().map(String::as_ref);
+        match Config::ignored_keys(cfg_file, &cli.opts.overrides) {
+            Ok(ignored) => warn_ignored_keys("config", &ignored),
+            Err(e) => fail(&e.to_string()),
+        }
+    }
+
+    if cli.print_config {
+        let cfg_file = cli.opts.config_file.as_ref().map(String::as_ref);
+        match Config::effective(cfg_file, &cli.opts.overrides) {
+            Ok(cfg) => println!("{}", serde_json::to_string_pretty(&cfg).unwrap()),
+            Err(e) => fail(&e.to_string()),
+        }
+        return;
+    }
+
+    match cli.command {
+        Subcommand::Play { record } => {
+            let mut input = Input::stdin();
+            if let Some(file) = record {
+                match File::create(&file) {
+                    Ok(f) => input.record(Box::new(f)),
+                    Err(e) => Log::panic(&format!("Error: can't create {}: {}", file, e)),
+                }
+            }
+            if let Err(e) = run_bot(input, Box::new(io::stdout()), &cli.opts) {
+                Log::panic(&format!("Error: {}", e));
+            }
+        }
+        Subcommand::Playback { file } => {
+            let input = open_transcript(&file);
+            if let Err(e) = run_bot(input, Box::new(io::stdout()), &cli.opts) {
+                Log::panic(&format!("Error: {}", e));
+            }
+        }
+        Subcommand::Simulate {
+            settings,
+            bots,
+            json,
+        } => simulate(settings, &bots, json, &cli.opts),
+        Subcommand::Tournament(args) => tournament(&args, &cli.opts),
+        Subcommand::Bench { file, runs } => bench(&file, runs, &cli.opts),
+        Subcommand::Tune {
+            candidates,
+            games,
+            settings,
+            threads,
+        } => tune(&candidates, games, settings, threads, &cli.opts),
+        Subcommand::Search {
+            spec,
+            out,
+            threads,
+            overrides,
+        } => search(&spec, &out, threads, &overrides, &cli.opts),
+        Subcommand::Ratings { file } => match RatingTable::load(&file) {
+            Ok(table) => print!("{}", table),
+            Err(e) => fail(&format!("can't read {}: {}", file, e)),
+        },
+        Subcommand::Gym { seats } => gym(&seats, &cli.opts),
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("Error: {}", message);
+    exit(1);
+}
+
+fn open_transcript(file: &str) -> Input {
+    match File::open(file) {
+        Ok(f) => Input::new(Box::new(BufReader::new(f))),
+        Err(e) => fail(&format!("can't open {}: {}", file, e)),
+    }
+}
+
+fn mode(settings: &GameSettings) -> GameMode {
+    GameMode {
+        players: settings.players,
+        width: settings.size,
+        height: settings.size,
+    }
+}
+
+fn parse_bots(specs: &[String], opts: &BotOptions) -> Vec<BotSpec> {
+    specs
+        .iter()
+        .map(|spec| BotSpec::parse(spec, opts).unwrap_or_else(|e| fail(&e)))
+        .collect()
+}
+
+fn check_configs(bots: &[BotSpec], settings: &GameSettings) {
+    for bot in bots {
+        match bot.check_config(&mode(settings)) {
+            Ok(ignored) => warn_ignored_keys(&bot.label, &ignored),
+            Err(e) => fail(&format!("{}: {}", bot.label, e)),
+        }
+    }
+}
+
+fn warn_ignored_keys(label: &str, keys: &[String]) {
+    for key in keys {
+        eprintln!("Warning: {}: ignoring unknown config key {}", label, key);
+    }
+}
+
+fn simulate(settings: GameSettings, specs: &[String], json: bool, opts: &BotOptions) {
+    let specs = parse_bots(specs, opts);
+    check_configs(&specs, &settings);
+
+    let mut bots = vec![];
+    for spec in &specs {
+        match spec.start() {
+            Ok(bot) => bots.push(bot),
+            Err(e) => fail(&format!("can't start '{}': {}", spec.label, e)),
+        }
+    }
+    let result = play_game(settings, &mut bots);
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&result).unwrap());
+        return;
+    }
+
+    println!(
+        "seed {}, {}x{}, {} turns",
+        settings.seed,
+        settings.size,
+        settings.size,
+        settings.max_turns()
+    );
+    for (id, p) in result.players.iter().enumerate() {
+        println!(
+            "  #{} {:>7} halite  player {}: {} ({}{})",
+            p.rank,
+            p.halite,
+            id,
+            p.name,
+            specs[id].label,
+            if p.crashed { ", crashed" } else { "" }
+        );
+        if p.invalid_orders > 0 {
+            println!("      {} invalid orders ignored", p.invalid_orders);
+        }
+    }
+}
+
+/// Summary of the times spent in one stage of the pipeline.
+fn timing_summary(name: &str, times: &mut [Duration]) -> String {
+    times.sort();
+    let ms = |d: Duration| d.as_secs() as f64 * 1e3 + f64::from(d.subsec_nanos()) * 1e-6;
+    let at = |q: f64| ms(times[((times.len() - 1) as f64 * q) as usize]);
+    let mean = times.iter().cloned().map(ms).sum::<f64>() / times.len() as f64;
+    format!(
+        "{:<14}{:>10.3}{:>10.3}{:>10.3}{:>10.3}",
+        name,
+        mean,
+        at(0.5),
+        at(0.95),
+        at(1.0)
+    )
+}
+
+fn bench(file: &str, runs: usize, opts: &BotOptions) {
+    Log::disable();
+
+    let mut update = vec![];
+    let mut think = vec![];
+    let mut finalize = vec![];
+    for _ in 0..runs {
+        let mut ai_mgr = AiManager::new();
+        let mut game = match GameState::new(open_transcript(file), Box::new(io::sink()), opts) {
+            Ok(game) => game,
+            Err(e) => fail(&format!("can't read the game start from {}: {}", file, e)),
+        };
+
+        loop {
+            let t0 = Instant::now();
+            match game.update_frame() {
+                Ok(()) => {}
+                Err(ref e) if e.is_eof() => break,
+                Err(e) => fail(&format!("can't read a frame from {}: {}", file, e)),
+            }
+            let t1 = Instant::now();
+            ai_mgr.think(&mut game);
+            let t2 = Instant::now();
+            if let Err(e) = game.finalize_frame(None) {
+                fail(&e.to_string());
+            }
+            let t3 = Instant::now();
+
+            update.push(t1 - t0);
+            think.push(t2 - t1);
+            finalize.push(t3 - t2);
+        }
+    }
+
+    if update.is_empty() {
+        fail(&format!("{} has no frames", file));
+    }
+
+    let mut total: Vec<Duration> = (0..update.len())
+        .map(|i| update[i] + think[i] + finalize[i])
+        .collect();
+    println!("{} turns in {} runs, times in ms", update.len(), runs);
+    println!(
+        "{:<14}{:>10}{:>10}{:>10}{:>10}",
+        "", "mean", "p50", "p95", "max"
+    );
+    println!("{}", timing_summary("update_frame", &mut update));
+    println!("{}", timing_summary("think", &mut think));
+    println!("{}", timing_summary("finalize", &mut finalize));
+    println!("{}", timing_summary("total", &mut total));
+}
+
+fn tournament(args: &TournamentArgs, opts: &BotOptions) {
+    // bots running side by side would write into the same log files
+    let opts = BotOptions {
+        quiet: true,
+        dump_file: None,
+        ..opts.clone()
+    };
+    let tournament = Tournament {
+        bots: parse_bots(&args.bots, &opts),
+        games: args.games,
+        size: args.settings.size,
+        players: args.settings.players,
+        seed: args.settings.seed,
+        threads: args.threads,
+        sprt: args.sprt,
+    };
+    check_configs(&tournament.bots, &args.settings);
+
+    // read the table first, so a broken file does not waste a whole tournament
+    let mut ratings = match args.ratings {
+        Some(ref file) => match RatingTable::load(file) {
+            Ok(table) => Some(table),
+            Err(e) => fail(&format!("can't read {}: {}", file, e)),
+        },
+        None => None,
+    };
+
+    let result = tournament.run(|report| {
+        if report.games % 10 == 0 && report.games < args.games {
+            let sprt = match report.sprt {
+                Some(ref sprt) => format!(", {}", sprt),
+                None => String::new(),
+            };
+            eprintln!(
+                "{}/{} games, win rate {:.1}%{}",
+                report.games,
+                args.games,
+                100.0 * report.win_rate,
+                sprt
+            );
+        }
+    });
+    let report = match result {
+        Ok(report) => report,
+        Err(e) => fail(&e.to_string()),
+    };
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    } else {
+        print!("{}", report);
+    }
+
+    if let (Some(file), Some(table)) = (args.ratings.as_ref(), ratings.as_mut()) {
+        for outcome in &report.outcomes {
+            let names: Vec<&str> = outcome
+                .seats
+                .iter()
+                .map(|&b| report.bots[b].as_ref())
+                .collect();
+            let ranks: Vec<usize> = outcome.players.iter().map(|p| p.rank).collect();
+            table.record_game(&names, &ranks);
+        }
+        if let Err(e) = table.save(file) {
+            fail(&format!("can't write {}: {}", file, e));
+        }
+        if !args.json {
+            print!("\n{}", table);
+        }
+    }
+}
+
+fn tune(
+    candidates: &[String],
+    games: usize,
+    settings: GameSettings,
+    threads: usize,
+    opts: &BotOptions,
+) {
+    let reference = BotOptions {
+        quiet: true,
+        dump_file: None,
+        ..opts.clone()
+    };
+
+    let tournaments: Vec<Tournament> = candidates
+        .iter()
+        .map(|change| {
+            let mut candidate = reference.clone();
+            candidate.overrides.push(change.clone());
+            Tournament {
+                bots: vec![
+                    BotSpec::local(change, candidate),
+                    BotSpec::local("reference", reference.clone()),
+                ],
+                games,
+                size: settings.size,
+                players: settings.players,
+                seed: settings.seed,
+                threads,
+                sprt: None,
+            }
+        })
+        .collect();
+
+    // catch typos before playing any games
+    for tournament in &tournaments {
+        check_configs(&tournament.bots, &settings);
+    }
+
+    println!(
+        "{} games per candidate, {}x{}, {} players, seed {}",
+        games, settings.size, settings.size, settings.players, settings.seed
+    );
+    for (change, tournament) in candidates.iter().zip(&tournaments) {
+        match tournament.run(|_| {}) {
+            Ok(report) => println!(
+                "  {:<40} {:>5.1}% wins, 95% CI {:.1}% .. {:.1}%",
+                change,
+                100.0 * report.win_rate,
+                100.0 * report.win_rate_ci95.0,
+                100.0 * report.win_rate_ci95.1
+            ),
+            Err(e) => fail(&e.to_string()),
+        }
+    }
+}
+
+fn format_params(params: &Params) -> String {
+    let params: Vec<_> = params
+        .iter()
+        .map(|(k, v)| format!("{}={:.4}", k, v))
+        .collect();
+    params.join(" ")
+}
+
+fn write_json<T: ::serde::Serialize>(file: &str, value: &T) {
+    let text = serde_json::to_string_pretty(value).unwrap() + "\n";
+    if let Err(e) = fs::write(file, text) {
+        fail(&format!("can't write {}: {}", file, e));
+    }
+}
+
+fn search(
+    spec_file: &str,
+    out: &str,
+    threads: usize,
+    overrides: &SpecOverrides,
+    opts: &BotOptions,
+) {
+    Log::disable();
+
+    let reference = BotOptions {
+        quiet: true,
+        dump_file: None,
+        ..opts.clone()
+    };
+    let optimizer = match TuningSpec::load(spec_file).and_then(|mut spec| {
+        overrides.apply(&mut spec);
+        Optimizer::new(spec, reference, threads)
+    }) {
+        Ok(optimizer) => optimizer,
+        Err(e) => fail(&e.to_string()),
+    };
+
+    let history_file = format!("{}.history.json", out.trim_end_matches(".json"));
+    let mut history = vec![];
+    let result = optimizer.run(|iteration| {
+        println!(
+            "iteration {}: {}",
+            history.len() + 1,
+            format_params(&iteration.mean)
+        );
+        for c in &iteration.candidates {
+            println!(
+                "  {:>5.1}%  {}",
+                100.0 * c.win_rate,
+                format_params(&c.params)
+            );
+        }
+        // keep the history on disk, so an interrupted search is not lost
+        history.push(iteration.clone());
+        let progress = SearchProgress {
+            best: None,
+            history: &history,
+        };
+        write_json(&history_file, &progress);
+    });
+    let result = match result {
+        Ok(result) => result,
+        Err(e) => fail(&e.to_string()),
+    };
+
+    match optimizer.config(&result.best.params) {
+        Ok(config) => write_json(out, &config),
+        Err(e) => fail(&e.to_string()),
+    }
+    let progress = SearchProgress {
+        best: Some(&result.best),
+        history: &result.history,
+    };
+    write_json(&history_file, &progress);
+    println!(
+        "result: {:.1}% wins against the reference with {}",
+        100.0 * result.best.win_rate,
+        optimizer.overrides(&result.best.params).join(" ")
+    );
+    println!("wrote {} and {}", out, history_file);
+}
+
+fn gym(specs: &[String], opts: &BotOptions) {
+    // stdout belongs to the protocol, and thousands of games would leave thousands of logs
+    let opts = BotOptions {
+        quiet: true,
+        dump_file: None,
+        ..opts.clone()
+    };
+    let seats = specs
+        .iter()
+        .map(|spec| Seat::parse(spec, &opts).unwrap_or_else(|e| fail(&e)))
+        .collect();
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    if let Err(e) = gym::serve(Env::new(seats), stdin.lock(), stdout.lock()) {
+        fail(&e.to_string());
+    }
+}
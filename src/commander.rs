@@ -2,8 +2,14 @@ use ai_manager::AiManager;
 use hlt::log::Log;
 use hlt::position::Position;
 use hlt::ShipId;
-use pda::{StackOp, StateStack};
-use ship_ai::{BuildDropoff, Collect, Deliver, ShipAiState};
+use pda::{Driver, Hook, Outcome, StackOp, StateStack, StateTime};
+use ship_ai::{
+    Collect, Deliver, GoBuildDropoff, GoHome, LearnedCollect, ShipAiState, ShipRole,
+    BUILD_DROPOFF_PRIORITY,
+};
+use spawn_model::want_ship;
+use std::collections::HashSet;
+use treasury::{Expense, Reservation, Treasury};
 use GameState;
 
 pub struct Commander {
@@ -13,19 +19,36 @@ pub struct Commander {
 impl Commander {
     pub fn new() -> Self {
         Commander {
-            states: StateStack::new(Box::new(MidGame)),
+            states: StateStack::new(Box::new(Opening)),
         }
     }
 
     pub fn think(&mut self, aimgr: &AiManager, world: &mut GameState) {
-        let op = self.current_state().step(aimgr, world);
-        self.states.transition(op);
+        self.states.run(&mut CommanderDriver { aimgr, world });
     }
 
     pub fn request_task(&mut self, id: ShipId, world: &GameState) -> Box<dyn ShipAiState> {
         self.current_state().request_task(id, world)
     }
 
+    /// The role of a ship spawned in the current phase.
+    pub fn ship_role(&mut self) -> ShipRole {
+        self.current_state().ship_role()
+    }
+
+    /// A ship learned the outcome of an action it was waiting for.
+    pub fn notify_outcome(
+        &mut self,
+        id: ShipId,
+        outcome: Outcome,
+        aimgr: &AiManager,
+        world: &mut GameState,
+    ) {
+        let op = self.current_state().on_outcome(id, outcome, world);
+        self.states
+            .transition(op, &mut CommanderDriver { aimgr, world });
+    }
+
     pub fn current_state(&mut self) -> &mut dyn CommanderState {
         self.states
             .top()
@@ -34,46 +57,247 @@ impl Commander {
     }
 }
 
+struct CommanderDriver<'a> {
+    aimgr: &'a AiManager,
+    world: &'a mut GameState,
+}
+
+impl<'a> Driver<Box<dyn CommanderState>> for CommanderDriver<'a> {
+    fn now(&self) -> usize {
+        self.world.game.turn_number
+    }
+
+    fn step(&mut self, state: &mut Box<dyn CommanderState>) -> StackOp<Box<dyn CommanderState>> {
+        state.step(self.aimgr, self.world)
+    }
+
+    fn hook(&mut self, state: &mut Box<dyn CommanderState>, hook: Hook, time: StateTime) {
+        match hook {
+            Hook::Enter => {
+                Log::log(&format!("Commander: entering {}", state.name()));
+                state.on_enter(self.aimgr, self.world)
+            }
+            Hook::Exit => {
+                Log::log(&format!(
+                    "Commander: leaving {} after {} turns",
+                    state.name(),
+                    time.turns
+                ));
+                state.on_exit(self.aimgr, self.world)
+            }
+            Hook::Resume => {
+                Log::log(&format!("Commander: resuming {}", state.name()));
+                state.on_resume(self.aimgr, self.world)
+            }
+        }
+    }
+}
+
 pub trait CommanderState {
-    fn step(&self, aimgr: &AiManager, world: &mut GameState) -> StackOp<Box<dyn CommanderState>>;
+    fn name(&self) -> &'static str;
+    fn step(
+        &mut self,
+        aimgr: &AiManager,
+        world: &mut GameState,
+    ) -> StackOp<Box<dyn CommanderState>>;
     fn request_task(&self, id: ShipId, world: &GameState) -> Box<dyn ShipAiState>;
+
+    fn ship_role(&self) -> ShipRole {
+        ShipRole::Regular
+    }
+
+    fn on_enter(&mut self, _aimgr: &AiManager, _world: &mut GameState) {}
+
+    fn on_exit(&mut self, _aimgr: &AiManager, _world: &mut GameState) {}
+
+    fn on_resume(&mut self, _aimgr: &AiManager, _world: &mut GameState) {}
+
+    fn on_outcome(
+        &mut self,
+        _id: ShipId,
+        _outcome: Outcome,
+        _world: &mut GameState,
+    ) -> StackOp<Box<dyn CommanderState>> {
+        StackOp::None
+    }
+}
+
+/// First turns of the game: spawn as fast as we can and let the ships spread out.
+///
+/// Hands over to `MidGame` once the initial fleet is complete or the map can no longer pay
+/// for more ships.
+struct Opening;
+
+impl CommanderState for Opening {
+    fn name(&self) -> &'static str {
+        "Opening"
+    }
+
+    fn step(
+        &mut self,
+        _aimgr: &AiManager,
+        world: &mut GameState,
+    ) -> StackOp<Box<dyn CommanderState>> {
+        if endgame_due(world) {
+            return StackOp::Override(Box::new(Endgame::new()));
+        }
+
+        let n_ships = world.me().ship_ids.len();
+        if n_ships >= world.config.strategy.opening_fleet_size
+            || halite_left_per_ship(world) <= world.game.constants.ship_cost
+        {
+            return StackOp::Override(Box::new(MidGame));
+        }
+
+        if enough_rounds_for_spawn(world) {
+            spawn(world);
+        }
+
+        StackOp::None
+    }
+
+    fn request_task(&self, id: ShipId, world: &GameState) -> Box<dyn ShipAiState> {
+        // everyone goes out to collect; Collect moves away from our structures when it has
+        // nothing better to do, which spreads the initial fleet.
+        collect_or_deliver(id, world)
+    }
+
+    fn ship_role(&self) -> ShipRole {
+        ShipRole::Opening
+    }
 }
 
+/// Regular operation: collect, deliver and keep the fleet at a size the map can pay for.
 struct MidGame;
 
 impl CommanderState for MidGame {
-    fn step(&self, aimgr: &AiManager, world: &mut GameState) -> StackOp<Box<dyn CommanderState>> {
-        let (max_pos, max_density) = world
-            .halite_density
-            .iter()
-            .enumerate()
-            .flat_map(|(i, row)| row.iter().enumerate().map(move |(j, &x)| (i, j, x)))
-            .max_by_key(|(_, _, x)| *x)
-            .map(|(i, j, x)| {
-                (
-                    Position {
-                        x: j as i32,
-                        y: i as i32,
-                    },
-                    x,
-                )
-            })
-            .unwrap();
-
-        let want_dropoff = world.avg_return_length
-            >= world.config.expansion.return_distance as f64
-            && max_density >= world.config.expansion.min_halite_density;
-
-        if want_dropoff {
-            // create a massive pheromone spike at a good dropoff location
-            //state.add_pheromone(max_pos, 100000.0);
-            world.add_pheromone(max_pos, 100000.0);
-        }
-
-        if want_dropoff && world.me().halite >= world.game.constants.dropoff_cost {
-            let id = aimgr
+    fn name(&self) -> &'static str {
+        "MidGame"
+    }
+
+    fn step(
+        &mut self,
+        _aimgr: &AiManager,
+        world: &mut GameState,
+    ) -> StackOp<Box<dyn CommanderState>> {
+        if endgame_due(world) {
+            return StackOp::Override(Box::new(Endgame::new()));
+        }
+
+        if let Some(site) = find_dropoff_site(world) {
+            return StackOp::Push(Box::new(Expansion::new(site)));
+        }
+
+        if want_ship(world) {
+            spawn(world);
+        }
+
+        StackOp::None
+    }
+
+    fn request_task(&self, id: ShipId, world: &GameState) -> Box<dyn ShipAiState> {
+        collect_or_deliver(id, world)
+    }
+}
+
+/// Ships take too long to return their cargo: build a new dropoff at the best site found by
+/// the dropoff planner.
+///
+/// Pushed on top of `MidGame`. The dropoff cost stays reserved until the dropoff is built or
+/// the plan is abandoned, then the phase pops itself.
+struct Expansion {
+    site: Position,
+    builder: Option<(ShipId, usize)>,
+}
+
+impl Expansion {
+    fn new(site: Position) -> Self {
+        Expansion {
+            site,
+            builder: None,
+        }
+    }
+
+    /// Call off the builder's trip, so it can neither be picked again nor build an unplanned
+    /// dropoff once it arrives.
+    fn dismiss_builder(&mut self, aimgr: &AiManager, world: &mut GameState) {
+        if let Some((id, _)) = self.builder.take() {
+            if aimgr.ships.contains_key(&id) {
+                aimgr
+                    .ship(id)
+                    .cancel_interrupt(BUILD_DROPOFF_PRIORITY, aimgr, world);
+            }
+        }
+    }
+}
+
+impl CommanderState for Expansion {
+    fn name(&self) -> &'static str {
+        "Expansion"
+    }
+
+    fn step(
+        &mut self,
+        aimgr: &AiManager,
+        world: &mut GameState,
+    ) -> StackOp<Box<dyn CommanderState>> {
+        if endgame_due(world) {
+            return StackOp::Override(Box::new(Endgame::new()));
+        }
+
+        if let Some((id, deadline)) = self.builder {
+            // a construction already under way is settled by its outcome instead
+            let late = world.game.turn_number > deadline
+                && aimgr.ships.contains_key(&id)
+                && !aimgr.ship(id).is_awaiting();
+            if !aimgr.ships.contains_key(&id) {
+                // completed dropoffs are reported through `on_outcome`, so the builder was
+                // destroyed on its way
+                Log::log(&format!("Commander: lost dropoff builder {:?}", id));
+                self.builder = None;
+            } else if late {
+                Log::log(&format!(
+                    "Commander: {:?} did not reach {:?} in time",
+                    id, self.site
+                ));
+                self.dismiss_builder(aimgr, world);
+            }
+        }
+
+        if self.builder.is_none() {
+            match find_dropoff_site(world) {
+                Some(site) if site != self.site => {
+                    world.treasury.release(Reservation::Dropoff(self.site));
+                    self.site = site;
+                }
+                Some(_) => {}
+                None => return StackOp::Done,
+            }
+        }
+
+        // keep the expected construction cost reserved while the builder is on its way
+        let cargo = self
+            .builder
+            .map(|(id, _)| world.get_ship(id).halite)
+            .unwrap_or(0);
+        let cost = Treasury::dropoff_cost_at(&world.game, self.site, cargo);
+        world
+            .treasury
+            .reserve(Reservation::Dropoff(self.site), cost);
+
+        // create a massive pheromone spike at the planned dropoff location
+        let spike = world.config.pheromones.dropoff_spike;
+        world.add_pheromone(self.site, spike);
+
+        let affordable = cost
+            <= world.treasury.available()
+                + world.treasury.reserved_for(Reservation::Dropoff(self.site));
+        if self.builder.is_none() && affordable {
+            let site = self.site;
+            let builder = aimgr
                 .ships
                 .keys()
+                .filter(|&&id| aimgr.ship(id).priority() < BUILD_DROPOFF_PRIORITY)
                 .filter(|&&id| {
                     world
                         .game
@@ -82,72 +306,196 @@ impl CommanderState for MidGame {
                         .structure
                         .is_none()
                 })
-                .filter(|&&id| {
-                    world.distance_to_nearest_dropoff(id)
-                        >= world.config.expansion.expansion_distance
-                })
-                .filter(|&&id| {
-                    world
-                        .ships_in_range(
-                            world.get_ship(id).position,
-                            world.config.expansion.ship_radius,
-                        )
-                        .count()
-                        >= world.config.expansion.n_ships
-                })
                 .map(|&id| {
-                    let p = world.get_ship(id).position;
                     (
                         id,
-                        world.halite_density[p.y as usize][p.x as usize],
-                        world.pheromones[p.y as usize][p.x as usize],
+                        world
+                            .game
+                            .map
+                            .calculate_distance(&world.get_ship(id).position, &site),
                     )
                 })
-                .filter(|&(_, density, _)| density >= world.config.expansion.min_halite_density)
-                .max_by_key(|&(_, _, phi)| phi as i64)
-                .map(|(id, _, _)| id);
+                .min_by_key(|&(id, dist)| (dist, id.0));
 
-            if let Some(id) = id {
-                Log::log(&format!("Commander: Instructing {:?} to build dropoff", id));
-                aimgr.ship(id).push_task(Box::new(BuildDropoff));
+            if let Some((id, dist)) = builder {
+                Log::log(&format!(
+                    "Commander: Instructing {:?} to build dropoff at {:?} (score {:?})",
+                    id,
+                    site,
+                    world.dropoff_planner.score_at(site)
+                ));
+                aimgr.ship(id).interrupt(
+                    Box::new(GoBuildDropoff::new(site)),
+                    BUILD_DROPOFF_PRIORITY,
+                    aimgr,
+                    world,
+                );
+                let cfg = &world.config.expansion;
+                let deadline = world.game.turn_number
+                    + cfg.builder_deadline_factor * dist
+                    + cfg.builder_deadline_slack;
+                self.builder = Some((id, deadline));
                 Log::log(&format!("{:?}", aimgr.ship(id)));
             }
         }
 
-        let mut want_ship = {
-            let bias = world.config.strategy.spawn_halite_floor;
-            let halite_left: usize = world
-                .game
-                .map
-                .iter()
-                .map(|cell| cell.halite.max(bias) - bias)
-                .sum();
-            let n_ships = world.game.ships.len() + 1;
+        // spawning only uses halite that is not reserved for the dropoff
+        if want_ship(world) {
+            spawn(world);
+        }
+
+        StackOp::None
+    }
+
+    fn request_task(&self, id: ShipId, world: &GameState) -> Box<dyn ShipAiState> {
+        collect_or_deliver(id, world)
+    }
+
+    fn on_exit(&mut self, aimgr: &AiManager, world: &mut GameState) {
+        // whether the dropoff was built or the plan abandoned, nothing remains to pay for
+        self.dismiss_builder(aimgr, world);
+        world.treasury.release(Reservation::Dropoff(self.site));
+    }
+
+    fn on_outcome(
+        &mut self,
+        id: ShipId,
+        outcome: Outcome,
+        world: &mut GameState,
+    ) -> StackOp<Box<dyn CommanderState>> {
+        match self.builder {
+            Some((builder, _)) if builder == id => {}
+            _ => return StackOp::None,
+        }
+
+        match outcome {
+            Outcome::Confirmed => {
+                Log::log(&format!("Commander: dropoff at {:?} completed", self.site));
+                self.builder = None;
+                StackOp::Done
+            }
+            Outcome::Failed => {
+                // the server did not accept the construction; release the reservation and
+                // re-plan on the next step, which picks a new site and builder if still wanted
+                Log::log(&format!(
+                    "Commander: {:?} failed to build at {:?}",
+                    id, self.site
+                ));
+                world.treasury.release(Reservation::Dropoff(self.site));
+                self.builder = None;
+                StackOp::None
+            }
+        }
+    }
+}
+
+/// The game is about to end: no more spawning, and every ship is sent home in time to unload
+/// its cargo before the last turn.
+struct Endgame {
+    sent_home: HashSet<ShipId>,
+}
 
-            (halite_left / n_ships > world.game.constants.ship_cost)
-                && world.rounds_left()
-                > world.game.map.width * world.config.strategy.spawn_min_rounds_left_factor
-        };
+impl Endgame {
+    fn new() -> Self {
+        Endgame {
+            sent_home: HashSet::new(),
+        }
+    }
+}
 
-        want_ship &= !want_dropoff
-            || world.me().halite
-            >= world.game.constants.dropoff_cost + world.game.constants.ship_cost;
+impl CommanderState for Endgame {
+    fn name(&self) -> &'static str {
+        "Endgame"
+    }
+
+    fn step(
+        &mut self,
+        aimgr: &AiManager,
+        world: &mut GameState,
+    ) -> StackOp<Box<dyn CommanderState>> {
+        self.sent_home.retain(|id| aimgr.ships.contains_key(id));
 
-        if want_ship && world.me().halite >= world.game.constants.ship_cost {
-            let pos = world.me().shipyard.position;
-            world.gns.notify_spawn(pos);
-            world.total_spent += world.game.constants.ship_cost; // assuming the spawn is always successful (it should be...)
+        for &id in aimgr.ships.keys() {
+            if self.sent_home.contains(&id) || !must_go_home(id, world) {
+                continue;
+            }
+            Log::log(&format!("Commander: Sending {:?} home", id));
+            aimgr.ship(id).override_task(Box::new(GoHome), aimgr, world);
+            self.sent_home.insert(id);
         }
 
         StackOp::None
     }
 
     fn request_task(&self, id: ShipId, world: &GameState) -> Box<dyn ShipAiState> {
-        let cargo = world.get_ship(id).halite;
-        if cargo < 500 {
-            Box::new(Collect)
+        if must_go_home(id, world) {
+            Box::new(GoHome)
         } else {
-            Box::new(Deliver::new())
+            collect_or_deliver(id, world)
         }
     }
 }
+
+fn collect_or_deliver(id: ShipId, world: &GameState) -> Box<dyn ShipAiState> {
+    let cargo = world.get_ship(id).halite;
+    if cargo < world.config.ships.deliver_cargo {
+        if world.policy.is_some() {
+            Box::new(LearnedCollect)
+        } else {
+            Box::new(Collect)
+        }
+    } else {
+        Box::new(Deliver::new())
+    }
+}
+
+/// Returns the best dropoff site if building a dropoff is worthwhile.
+fn find_dropoff_site(world: &GameState) -> Option<Position> {
+    if world.statistics.avg_return_length() < world.config.expansion.return_distance as f64 {
+        return None;
+    }
+    world.dropoff_planner.best_site().map(|(pos, _)| pos)
+}
+
+/// Halite above the spawn floor per ship, counting a new one.
+pub fn halite_left_per_ship(world: &GameState) -> usize {
+    let bias = world.config.strategy.spawn_halite_floor;
+    let halite_left: usize = world
+        .game
+        .map
+        .iter()
+        .map(|cell| cell.halite.max(bias) - bias)
+        .sum();
+    let n_ships = world.game.ships.len() + 1;
+    halite_left / n_ships
+}
+
+pub fn enough_rounds_for_spawn(world: &GameState) -> bool {
+    world.rounds_left() > world.game.map.width * world.config.strategy.spawn_min_rounds_left_factor
+}
+
+fn spawn(world: &mut GameState) -> bool {
+    if !world
+        .treasury
+        .try_spend(Expense::Spawn, world.game.constants.ship_cost)
+    {
+        return false;
+    }
+    let pos = world.me().shipyard.position;
+    world.gns.notify_spawn(pos);
+    true
+}
+
+/// A ship must head home now if it would otherwise not make it back before the game ends.
+/// The safety margin grows with the fleet size, because ships queue up at the structures.
+pub fn must_go_home(id: ShipId, world: &GameState) -> bool {
+    let dist = world.get_return_distance(world.get_ship(id).position);
+    world.rounds_left()
+        <= dist
+            + (world.me().ship_ids.len() * world.config.navigation.go_home_safety_factor)
+                / (1 + world.me().dropoff_ids.len())
+}
+
+fn endgame_due(world: &GameState) -> bool {
+    world.my_ships().any(|id| must_go_home(id, world))
+}
@@ -1,9 +1,26 @@
-use serde_json;
-use std::fs::File;
-use std::io::BufReader;
+use hlt::direction::Direction;
+use hlt::log::Log;
+use serde_json::{self, Map, Value};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
 
-#[derive(Deserialize)]
+/// Version of the config schema. Files without a `version` key are version 1, the format of
+/// the submission configs v14 to v24.
+pub const CONFIG_VERSION: u64 = 2;
+
+/// The default config, baked into the binary so it plays without any files around.
+const EMBEDDED: &str = include_str!("../config.json");
+
+/// Every field has a default, so config files only need to contain what they change.
+///
+/// A config file may also contain `profiles`, partial configs that are laid over the base
+/// config depending on the game mode (see `GameMode::profile_keys`).
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
+    pub version: u64,
     pub strategy: Strategy,
     pub expansion: Expansion,
     pub navigation: Navigation,
@@ -12,61 +29,822 @@ pub struct Config {
     pub ships: Ships,
 }
 
-#[derive(Deserialize)]
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            version: CONFIG_VERSION,
+            strategy: Strategy::default(),
+            expansion: Expansion::default(),
+            navigation: Navigation::default(),
+            pheromones: Pheromones::default(),
+            statistics: Statistics::default(),
+            ships: Ships::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct Strategy {
     pub spawn_halite_floor: usize,
     pub spawn_min_rounds_left_factor: usize,
+    pub opening_fleet_size: usize,
+    pub spawn_min_samples: usize,
+    pub spawn_ramp_up_turns: usize,
+    pub spawn_margin: usize,
 }
 
-#[derive(Deserialize)]
+impl Default for Strategy {
+    fn default() -> Self {
+        Strategy {
+            spawn_halite_floor: 0,
+            spawn_min_rounds_left_factor: 2,
+            opening_fleet_size: 10,
+            spawn_min_samples: 40,
+            spawn_ramp_up_turns: 10,
+            spawn_margin: 0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct Expansion {
     pub expansion_distance: usize,
     pub return_distance: usize,
     pub min_halite_density: i32,
     pub ship_radius: usize,
     pub n_ships: usize,
+
+    pub density_radii: Vec<i32>,
+    pub density_weight: f64,
+    pub own_distance_weight: f64,
+    pub enemy_structure_radius: usize,
+    pub enemy_structure_weight: f64,
+    pub contest_radius: usize,
+    pub contested_weight: f64,
+    pub return_savings_weight: f64,
+
+    /// Radius of the halite density map kept by the game state.
+    pub halite_density_radius: i32,
+    /// A builder has `factor * distance + slack` turns to reach its site.
+    pub builder_deadline_factor: usize,
+    pub builder_deadline_slack: usize,
 }
 
-#[derive(Deserialize)]
+impl Default for Expansion {
+    fn default() -> Self {
+        Expansion {
+            expansion_distance: 15,
+            return_distance: 10,
+            min_halite_density: 100,
+            ship_radius: 12,
+            n_ships: 3,
+
+            density_radii: vec![3, 5, 8],
+            density_weight: 1.0,
+            own_distance_weight: 2.0,
+            enemy_structure_radius: 10,
+            enemy_structure_weight: 10.0,
+            contest_radius: 6,
+            contested_weight: 100.0,
+            return_savings_weight: 1.0,
+
+            halite_density_radius: 5,
+            builder_deadline_factor: 2,
+            builder_deadline_slack: 10,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct Navigation {
     pub return_step_cost: i32,
     pub go_home_safety_factor: usize,
+    /// Direction (`n`, `s`, `e`, `w`) from which the return map does not approach our
+    /// structures, so ships leaving them always find a way out. `null` disables the rule.
+    pub return_map_exit: Option<char>,
+}
+
+impl Default for Navigation {
+    fn default() -> Self {
+        Navigation {
+            return_step_cost: 100,
+            go_home_safety_factor: 1,
+            return_map_exit: Some('e'),
+        }
+    }
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct Pheromones {
-    pub evaporation_rate: f64,
     pub diffusion_coefficient: f64,
     pub decay_rate: f64,
     pub ship_absorbtion: f64,
     pub ship_evaporation: f64,
     pub time_step: f64,
     pub n_steps: usize,
+
+    /// Emitted by a ship that spotted prey, to attract its friends.
+    pub prey_attraction: f64,
+    /// Emitted at a planned dropoff site.
+    pub dropoff_spike: f64,
+    /// Fraction of their cargo opponent ships emit.
+    pub enemy_cargo_emission: f64,
 }
 
-#[derive(Deserialize)]
-pub struct Ships {
-    pub greedy_prefer_stay_factor: usize,
-    pub greedy_harvest_limit: usize,
-    pub greedy_seek_limit: usize,
-    pub greedy_pheromone_weight: f64,
+impl Default for Pheromones {
+    fn default() -> Self {
+        Pheromones {
+            diffusion_coefficient: 10.0,
+            decay_rate: 0.25,
+            ship_absorbtion: 0.15,
+            ship_evaporation: 0.0,
+            time_step: 0.02,
+            n_steps: 50,
 
-    pub greedy_move_cost_factor: f64,
-    pub seek_greed_factor: f64,
-    pub seek_return_cost_factor: f64,
-    pub seek_pheromone_factor: f64,
+            prey_attraction: 1000.0,
+            dropoff_spike: 100000.0,
+            enemy_cargo_emission: 0.1,
+        }
+    }
+}
 
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Ships {
     pub carefulness_limit: i32,
+
+    /// `Collect` finishes at this cargo...
+    pub return_cargo: i32,
+    /// ... or at this cargo if the pheromone level drops below `early_return_pheromone`.
+    pub early_return_cargo: i32,
+    pub early_return_pheromone: f64,
+    /// Ships with at least this cargo are sent to deliver rather than collect.
+    pub deliver_cargo: usize,
+
+    pub aggressiveness: f64,
+    /// How far to look for friends that pick up the cargo of a rammed ship if no other
+    /// opponent is around.
+    pub prey_escort_radius: usize,
+
+    pub utility: Utility,
+
+    pub controllers: Controllers,
+
+    pub policy: Policy,
 }
 
-#[derive(Deserialize)]
+impl Default for Ships {
+    fn default() -> Self {
+        Ships {
+            carefulness_limit: 500,
+
+            return_cargo: 950,
+            early_return_cargo: 500,
+            early_return_pheromone: 10.0,
+            deliver_cargo: 500,
+
+            aggressiveness: 10.0,
+            prey_escort_radius: 10,
+
+            utility: Utility::default(),
+
+            controllers: Controllers::default(),
+
+            policy: Policy::default(),
+        }
+    }
+}
+
+/// Responses to the considerations that score `Collect` moves.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Utility {
+    pub pheromone: Response,
+    pub local_halite: Response,
+    pub greedy_harvest: Response,
+    pub structure: Response,
+    pub loitering: Response,
+    pub return_drift: Response,
+    pub threat: Response,
+    pub prey: Response,
+}
+
+impl Default for Utility {
+    fn default() -> Self {
+        Utility {
+            pheromone: Response::linear(1.0),
+            local_halite: Response {
+                weight: 1.0,
+                curve: Curve::Ramp {
+                    threshold: 0.0,
+                    slope: 1.0,
+                },
+            },
+            greedy_harvest: Response::greedy_harvest(50.0),
+            structure: Response::linear(-9999999.0),
+            loitering: Response::linear(-9999999.0),
+            return_drift: Response::linear(0.1),
+            threat: Response::linear(-1e12),
+            prey: Response::linear(0.01),
+        }
+    }
+}
+
+/// Utility of a consideration is `weight * curve(input)`.
+#[derive(Serialize, Deserialize)]
+pub struct Response {
+    pub weight: f64,
+    pub curve: Curve,
+}
+
+impl Response {
+    fn linear(weight: f64) -> Self {
+        Response {
+            weight,
+            curve: Curve::Linear {
+                slope: 1.0,
+                intercept: 0.0,
+            },
+        }
+    }
+
+    fn greedy_harvest(limit: f64) -> Self {
+        Response {
+            weight: 1.0,
+            curve: Curve::Step {
+                threshold: limit,
+                below: 0.0,
+                above: 1000.0,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Curve {
+    Linear {
+        slope: f64,
+        intercept: f64,
+    },
+    /// Zero up to the threshold, then linear.
+    Ramp {
+        threshold: f64,
+        slope: f64,
+    },
+    Step {
+        threshold: f64,
+        below: f64,
+        above: f64,
+    },
+    Logistic {
+        midpoint: f64,
+        steepness: f64,
+    },
+}
+
+/// Which architecture controls the ships of each role.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Controllers {
+    pub opening: ControllerKind,
+    pub regular: ControllerKind,
+}
+
+impl Default for Controllers {
+    fn default() -> Self {
+        Controllers {
+            opening: ControllerKind::PushdownAutomaton,
+            regular: ControllerKind::PushdownAutomaton,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum ControllerKind {
+    #[serde(rename = "pda")]
+    PushdownAutomaton,
+    #[serde(rename = "bt")]
+    BehaviorTree,
+}
+
+/// A learned collection policy (see `neural::Policy`).
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Policy {
+    /// Network weights saved with `np.savez`, e.g. `machine_learning/ai1.npz`. Collecting ships
+    /// follow the network if this is set.
+    pub weights: Option<String>,
+    /// Factor for the halite in the network's input, e.g. 0.001 for a network trained on
+    /// halite / 1000.
+    pub input_scale: f64,
+    /// `plan_move` cost of a move per unit of its negative log probability.
+    pub cost_scale: f64,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy {
+            weights: None,
+            input_scale: 1.0,
+            cost_scale: 100.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct Statistics {
     pub halite_collection_window: usize,
+    /// Weight of the previous average when a new return trip is recorded.
+    pub return_length_update_rate: f64,
+}
+
+impl Default for Statistics {
+    fn default() -> Self {
+        Statistics {
+            halite_collection_window: 100,
+            return_length_update_rate: 0.9,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(String, io::Error),
+    Parse(serde_json::Error),
+    /// The file was written for a newer version of the bot.
+    UnsupportedVersion(u64),
+    /// Values out of their valid range, one message each.
+    Invalid(Vec<String>),
+    /// A command line override is not of the form `key=value`.
+    MalformedOverride(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(file, e) => write!(f, "can't read {}: {}", file, e),
+            ConfigError::Parse(e) => write!(f, "malformed config: {}", e),
+            ConfigError::UnsupportedVersion(v) => write!(
+                f,
+                "config version {} is newer than the supported version {}",
+                v, CONFIG_VERSION
+            ),
+            ConfigError::Invalid(errors) => write!(f, "invalid config: {}", errors.join("; ")),
+            ConfigError::MalformedOverride(s) => {
+                write!(f, "override '{}' is not of the form key=value", s)
+            }
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+/// The kind of game, which selects the config profiles.
+#[derive(Debug, Copy, Clone)]
+pub struct GameMode {
+    pub players: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl GameMode {
+    /// Profiles that apply to this mode, from least to most specific, e.g. `2p`, `32x32` and
+    /// `2p-32x32`.
+    pub fn profile_keys(&self) -> Vec<String> {
+        let players = format!("{}p", self.players);
+        let size = format!("{}x{}", self.width, self.height);
+        let both = format!("{}-{}", players, size);
+        vec![players, size, both]
+    }
 }
 
 impl Config {
-    pub fn from_file(file: &str) -> Self {
-        let f = File::open(file).expect(&format!("Error loading file {:?}", file));
-        serde_json::from_reader(BufReader::new(f)).expect("Deserialization Error")
+    /// Build the config for a game: the embedded default config, overlaid by the config file
+    /// (if any), then the profiles for the game mode, and finally the command line overrides
+    /// (`key.path=value`).
+    pub fn load(
+        file: Option<&str>,
+        mode: &GameMode,
+        overrides: &[String],
+    ) -> Result<Self, ConfigError> {
+        let mut value = base_layers(file)?;
+
+        let profiles = take_profiles(&mut value);
+        for key in mode.profile_keys() {
+            if let Some(profile) = profiles.get(&key) {
+                Log::log(&format!("Config: applying profile {}", key));
+                merge(&mut value, profile);
+            }
+        }
+
+        for o in overrides {
+            Log::log(&format!("Config: overriding {}", o));
+            merge(&mut value, &parse_override(o)?);
+        }
+
+        Config::from_value(value)
+    }
+
+    /// The effective config outside of any game mode, with defaults filled in and the profiles
+    /// listed separately. Command line overrides are applied to the base config; in a game
+    /// they also take precedence over the profiles.
+    pub fn effective(file: Option<&str>, overrides: &[String]) -> Result<Value, ConfigError> {
+        let mut value = base_layers(file)?;
+        let profiles = take_profiles(&mut value);
+        for o in overrides {
+            merge(&mut value, &parse_override(o)?);
+        }
+
+        let config = Config::from_value(value)?;
+        let mut value = serde_json::to_value(&config).map_err(ConfigError::Parse)?;
+        if !profiles.is_null() {
+            value["profiles"] = profiles;
+        }
+        Ok(value)
+    }
+
+    /// Keys of the config file, its profiles and the overrides that the bot does not know and
+    /// ignores. Games only log them, so the command line reports them with this.
+    pub fn ignored_keys(
+        file: Option<&str>,
+        overrides: &[String],
+    ) -> Result<Vec<String>, ConfigError> {
+        let mut value = base_layers(file)?;
+        let profiles = take_profiles(&mut value);
+        for o in overrides {
+            merge(&mut value, &parse_override(o)?);
+        }
+        let (_, mut ignored) = parse(&value)?;
+
+        if let Value::Object(ref profiles) = profiles {
+            for (key, profile) in profiles {
+                let mut layered = value.clone();
+                merge(&mut layered, profile);
+                let (config, _) = parse(&layered)?;
+                let known = serde_json::to_value(&config).map_err(ConfigError::Parse)?;
+                for k in unknown_keys(profile, &known) {
+                    ignored.push(format!("profiles.{}.{}", key, k));
+                }
+            }
+        }
+        Ok(ignored)
+    }
+
+    /// Migrate, deserialize and validate a config. Keys the bot does not know are logged and
+    /// otherwise ignored.
+    pub fn from_value(mut value: Value) -> Result<Self, ConfigError> {
+        migrate(&mut value)?;
+
+        let (config, ignored) = parse(&value)?;
+        for key in ignored {
+            Log::log(&format!("Config: ignoring unknown key {}", key));
+        }
+
+        let errors = config.validate();
+        if errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(ConfigError::Invalid(errors))
+        }
+    }
+
+    /// Check all values against their valid ranges.
+    pub fn validate(&self) -> Vec<String> {
+        let mut c = Checker(vec![]);
+
+        let s = &self.strategy;
+        c.require(
+            s.spawn_min_samples > 0,
+            "strategy.spawn_min_samples",
+            "positive",
+            &s.spawn_min_samples,
+        );
+
+        let e = &self.expansion;
+        c.require(
+            !e.density_radii.is_empty(),
+            "expansion.density_radii",
+            "non-empty",
+            &e.density_radii,
+        );
+        c.require(
+            e.density_radii.iter().all(|&r| r >= 0),
+            "expansion.density_radii",
+            "non-negative",
+            &e.density_radii,
+        );
+        c.require(
+            e.halite_density_radius >= 0,
+            "expansion.halite_density_radius",
+            "non-negative",
+            &e.halite_density_radius,
+        );
+
+        let n = &self.navigation;
+        c.require(
+            n.return_step_cost >= 0,
+            "navigation.return_step_cost",
+            "non-negative",
+            &n.return_step_cost,
+        );
+        c.require(
+            match n.return_map_exit {
+                None => true,
+                Some(c) => {
+                    matches!(Direction::from_char_encoding(c), Some(d) if d != Direction::Still)
+                }
+            },
+            "navigation.return_map_exit",
+            "one of n, s, e, w or null",
+            &n.return_map_exit,
+        );
+
+        let p = &self.pheromones;
+        c.require(
+            p.time_step > 0.0,
+            "pheromones.time_step",
+            "positive",
+            &p.time_step,
+        );
+        c.require(
+            p.diffusion_coefficient >= 0.0,
+            "pheromones.diffusion_coefficient",
+            "non-negative",
+            &p.diffusion_coefficient,
+        );
+        c.require(
+            p.diffusion_coefficient * p.time_step <= 0.25,
+            "pheromones.diffusion_coefficient * pheromones.time_step",
+            "at most 0.25 for a stable simulation",
+            &(p.diffusion_coefficient * p.time_step),
+        );
+        c.require(
+            p.decay_rate >= 0.0,
+            "pheromones.decay_rate",
+            "non-negative",
+            &p.decay_rate,
+        );
+        c.require(
+            p.ship_absorbtion >= 0.0,
+            "pheromones.ship_absorbtion",
+            "non-negative",
+            &p.ship_absorbtion,
+        );
+        c.require(
+            p.ship_evaporation >= 0.0,
+            "pheromones.ship_evaporation",
+            "non-negative",
+            &p.ship_evaporation,
+        );
+        c.require(
+            p.enemy_cargo_emission >= 0.0,
+            "pheromones.enemy_cargo_emission",
+            "non-negative",
+            &p.enemy_cargo_emission,
+        );
+
+        let st = &self.statistics;
+        c.require(
+            st.halite_collection_window > 0,
+            "statistics.halite_collection_window",
+            "positive",
+            &st.halite_collection_window,
+        );
+        c.require(
+            (0.0..=1.0).contains(&st.return_length_update_rate),
+            "statistics.return_length_update_rate",
+            "between 0 and 1",
+            &st.return_length_update_rate,
+        );
+
+        let sh = &self.ships;
+        c.require(
+            sh.return_cargo > 0,
+            "ships.return_cargo",
+            "positive",
+            &sh.return_cargo,
+        );
+        c.require(
+            sh.early_return_cargo <= sh.return_cargo,
+            "ships.early_return_cargo",
+            "at most ships.return_cargo",
+            &sh.early_return_cargo,
+        );
+        c.require(
+            sh.aggressiveness >= 0.0,
+            "ships.aggressiveness",
+            "non-negative",
+            &sh.aggressiveness,
+        );
+
+        let u = &sh.utility;
+        for &(name, r) in &[
+            ("pheromone", &u.pheromone),
+            ("local_halite", &u.local_halite),
+            ("greedy_harvest", &u.greedy_harvest),
+            ("structure", &u.structure),
+            ("loitering", &u.loitering),
+            ("return_drift", &u.return_drift),
+            ("threat", &u.threat),
+            ("prey", &u.prey),
+        ] {
+            c.require(
+                r.weight.is_finite(),
+                &format!("ships.utility.{}.weight", name),
+                "finite",
+                &r.weight,
+            );
+        }
+
+        let po = &sh.policy;
+        c.require(
+            po.input_scale.is_finite(),
+            "ships.policy.input_scale",
+            "finite",
+            &po.input_scale,
+        );
+        c.require(
+            po.cost_scale > 0.0,
+            "ships.policy.cost_scale",
+            "positive",
+            &po.cost_scale,
+        );
+
+        c.0
+    }
+}
+
+struct Checker(Vec<String>);
+
+impl Checker {
+    fn require(&mut self, ok: bool, key: &str, rule: &str, value: &dyn fmt::Debug) {
+        if !ok {
+            self.0
+                .push(format!("{} must be {} (got {:?})", key, rule, value));
+        }
+    }
+}
+
+/// The embedded config with the config file laid over it. Each layer is migrated on its own
+/// so that old files still override the right keys.
+fn base_layers(file: Option<&str>) -> Result<Value, ConfigError> {
+    let mut value = serde_json::from_str(EMBEDDED).map_err(ConfigError::Parse)?;
+    migrate(&mut value)?;
+
+    if let Some(file) = file {
+        let text = fs::read_to_string(file).map_err(|e| ConfigError::Io(file.to_string(), e))?;
+        let mut layer = serde_json::from_str(&text).map_err(ConfigError::Parse)?;
+        migrate(&mut layer)?;
+        merge(&mut value, &layer);
+    }
+
+    Ok(value)
+}
+
+fn take_profiles(value: &mut Value) -> Value {
+    value
+        .as_object_mut()
+        .and_then(|root| root.remove("profiles"))
+        .unwrap_or(Value::Null)
+}
+
+/// Recursively lay `overlay` over `base`. Objects are merged key by key, everything else is
+/// replaced.
+fn merge(base: &mut Value, overlay: &Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, v) in overlay {
+                match base.get_mut(key) {
+                    Some(b) => merge(b, v),
+                    None => {
+                        base.insert(key.clone(), v.clone());
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay.clone(),
+    }
+}
+
+/// Turn `a.b.c=value` into `{"a": {"b": {"c": value}}}`. Values that are not valid JSON are
+/// taken as strings.
+fn parse_override(s: &str) -> Result<Value, ConfigError> {
+    let mut parts = s.splitn(2, '=');
+    let (path, raw) = match (parts.next(), parts.next()) {
+        (Some(path), Some(raw)) if !path.is_empty() => (path, raw),
+        _ => return Err(ConfigError::MalformedOverride(s.to_string())),
+    };
+
+    let mut value = serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string()));
+    for key in path.rsplit('.') {
+        let mut object = Map::new();
+        object.insert(key.to_string(), value);
+        value = Value::Object(object);
+    }
+    Ok(value)
+}
+
+/// Deserialize a migrated config, without validating it, and list the keys it ignored.
+fn parse(value: &Value) -> Result<(Config, Vec<String>), ConfigError> {
+    let config: Config = serde_json::from_value(value.clone()).map_err(ConfigError::Parse)?;
+    let known = serde_json::to_value(&config).map_err(ConfigError::Parse)?;
+    let ignored = unknown_keys(value, &known);
+    Ok((config, ignored))
+}
+
+/// Paths of all keys in `value` that do not appear in `known`.
+fn unknown_keys(value: &Value, known: &Value) -> Vec<String> {
+    fn walk(value: &Value, known: &Value, path: &str, out: &mut Vec<String>) {
+        if let (Value::Object(value), Value::Object(known)) = (value, known) {
+            for (key, v) in value {
+                let p = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                match known.get(key) {
+                    Some(k) => walk(v, k, &p, out),
+                    None => out.push(p),
+                }
+            }
+        }
+    }
+
+    let mut out = vec![];
+    walk(value, known, "", &mut out);
+    out
+}
+
+/// Bring a config of any older version up to `CONFIG_VERSION`.
+fn migrate(value: &mut Value) -> Result<(), ConfigError> {
+    let root = match value.as_object_mut() {
+        Some(root) => root,
+        None => {
+            return Err(ConfigError::Invalid(vec![
+                "config must be a JSON object".to_string()
+            ]))
+        }
+    };
+
+    let version = root.get("version").and_then(Value::as_u64).unwrap_or(1);
+    if version > CONFIG_VERSION {
+        return Err(ConfigError::UnsupportedVersion(version));
+    }
+    if version < 2 {
+        Log::log("Config: migrating from version 1");
+        migrate_v1(root);
+    }
+
+    root.insert("version".to_string(), CONFIG_VERSION.into());
+    Ok(())
+}
+
+/// Version 2 replaced the `greedy_*` and `seek_*` ship parameters by utility considerations
+/// and dropped parameters the bot no longer used.
+fn migrate_v1(root: &mut Map<String, Value>) {
+    const UNUSED: &[(&str, &str)] = &[
+        ("pheromones", "evaporation_rate"),
+        ("ships", "greedy_prefer_stay_factor"),
+        ("ships", "greedy_seek_limit"),
+        ("ships", "greedy_pheromone_weight"),
+        ("ships", "greedy_move_cost_factor"),
+        ("ships", "seek_greed_factor"),
+        ("ships", "seek_return_cost_factor"),
+        ("ships", "seek_pheromone_factor"),
+    ];
+
+    for &(section, key) in UNUSED {
+        let removed = root
+            .get_mut(section)
+            .and_then(Value::as_object_mut)
+            .and_then(|s| s.remove(key));
+        if removed.is_some() {
+            Log::log(&format!("Config: dropping unused key {}.{}", section, key));
+        }
+    }
+
+    let ships = match root.get_mut("ships").and_then(Value::as_object_mut) {
+        Some(ships) => ships,
+        None => return,
+    };
+
+    if let Some(limit) = ships
+        .remove("greedy_harvest_limit")
+        .and_then(|l| l.as_f64())
+    {
+        let utility = ships
+            .entry("utility".to_string())
+            .or_insert_with(|| Value::Object(Map::new()));
+        if let Some(utility) = utility.as_object_mut() {
+            let response =
+                serde_json::to_value(Response::greedy_harvest(limit)).expect("response serializes");
+            utility.insert("greedy_harvest".to_string(), response);
+        }
     }
 }
@@ -0,0 +1,195 @@
+use config;
+use hlt::game::Game;
+use hlt::map_cell::Structure;
+use hlt::position::Position;
+
+/// Scores every map cell as a potential dropoff location.
+///
+/// A site is only a candidate if it is far enough away from our existing structures, dense
+/// enough in halite and has enough of our ships around. Candidates are ranked by
+/// - halite density averaged over several radii,
+/// - distance to our structures (further sites take longer to reach),
+/// - proximity of enemy structures,
+/// - how contested the area is by enemy ships, and
+/// - how many steps our current ships would save on their way home.
+pub struct DropoffPlanner {
+    width: usize,
+    height: usize,
+    density: Vec<Vec<f64>>,
+    scores: Vec<Vec<Option<f64>>>,
+    best: Option<(Position, f64)>,
+}
+
+impl DropoffPlanner {
+    pub fn new(width: usize, height: usize) -> Self {
+        DropoffPlanner {
+            width,
+            height,
+            density: vec![vec![0.0; width]; height],
+            scores: vec![vec![None; width]; height],
+            best: None,
+        }
+    }
+
+    pub fn update_frame(&mut self, game: &Game, cfg: &config::Expansion) {
+        self.compute_density(game, &cfg.density_radii);
+
+        let me = &game.players[game.my_id.0];
+
+        let mut own_structures = vec![me.shipyard.position];
+        own_structures.extend(me.dropoff_ids.iter().map(|id| game.dropoffs[id].position));
+
+        let mut enemy_structures: Vec<_> = game
+            .players
+            .iter()
+            .filter(|p| p.id != game.my_id)
+            .map(|p| p.shipyard.position)
+            .collect();
+        enemy_structures.extend(
+            game.dropoffs
+                .values()
+                .filter(|d| d.owner != game.my_id)
+                .map(|d| d.position),
+        );
+
+        let nearest = |structures: &[Position], pos: &Position| {
+            structures
+                .iter()
+                .map(|s| game.map.calculate_distance(s, pos))
+                .min()
+                .unwrap_or(usize::MAX)
+        };
+
+        let my_ships: Vec<_> = me
+            .ship_ids
+            .iter()
+            .map(|id| game.ships[id].position)
+            .map(|p| (p, nearest(&own_structures, &p)))
+            .collect();
+        let enemy_ships: Vec<_> = game
+            .ships
+            .values()
+            .filter(|ship| ship.owner != game.my_id)
+            .map(|ship| ship.position)
+            .collect();
+
+        self.best = None;
+
+        for i in 0..self.height {
+            for j in 0..self.width {
+                self.scores[i][j] = None;
+
+                let pos = Position {
+                    x: j as i32,
+                    y: i as i32,
+                };
+
+                if game.map.cells[i][j].structure != Structure::None {
+                    continue;
+                }
+
+                let own_dist = nearest(&own_structures, &pos);
+                if own_dist < cfg.expansion_distance {
+                    continue;
+                }
+
+                let density = self.density[i][j];
+                if density < cfg.min_halite_density as f64 {
+                    continue;
+                }
+
+                let mut n_mine = 0;
+                let mut return_savings = 0;
+                for &(p, home_dist) in &my_ships {
+                    let d = game.map.calculate_distance(&p, &pos);
+                    if d <= cfg.ship_radius {
+                        n_mine += 1;
+                    }
+                    return_savings += home_dist.saturating_sub(d);
+                }
+                if n_mine < cfg.n_ships {
+                    continue;
+                }
+
+                let n_enemy = enemy_ships
+                    .iter()
+                    .filter(|p| game.map.calculate_distance(p, &pos) <= cfg.contest_radius)
+                    .count();
+                let contested = n_enemy as f64 / (n_enemy + n_mine).max(1) as f64;
+
+                let enemy_proximity = cfg
+                    .enemy_structure_radius
+                    .saturating_sub(nearest(&enemy_structures, &pos));
+
+                let score = density * cfg.density_weight
+                    - (own_dist - cfg.expansion_distance) as f64 * cfg.own_distance_weight
+                    - enemy_proximity as f64 * cfg.enemy_structure_weight
+                    - contested * cfg.contested_weight
+                    + return_savings as f64 * cfg.return_savings_weight;
+
+                self.scores[i][j] = Some(score);
+
+                match self.best {
+                    Some((_, best)) if best >= score => {}
+                    _ => self.best = Some((pos, score)),
+                }
+            }
+        }
+    }
+
+    /// The highest scoring candidate site of the current frame.
+    pub fn best_site(&self) -> Option<(Position, f64)> {
+        self.best
+    }
+
+    /// Score of a site in the current frame, or `None` if it is not a candidate.
+    pub fn score_at(&self, pos: Position) -> Option<f64> {
+        let (w, h) = (self.width as i32, self.height as i32);
+        let x = ((pos.x % w) + w) % w;
+        let y = ((pos.y % h) + h) % h;
+        self.scores[y as usize][x as usize]
+    }
+
+    fn compute_density(&mut self, game: &Game, radii: &[i32]) {
+        for row in &mut self.density {
+            for d in row {
+                *d = 0.0;
+            }
+        }
+
+        if radii.is_empty() {
+            return;
+        }
+
+        for &r in radii {
+            let n = 2 * r * (r + 1) + 1; // number of pixels within manhatten distance of r
+            for (i, row) in self.density.iter_mut().enumerate() {
+                for (j, d) in row.iter_mut().enumerate() {
+                    let mut sum = 0;
+                    for a in -r..=r {
+                        for b in -r..=r {
+                            if a.abs() + b.abs() > r {
+                                continue;
+                            }
+                            sum += game
+                                .map
+                                .at_position(&Position {
+                                    x: j as i32 - b,
+                                    y: i as i32 - a,
+                                })
+                                .halite;
+                        }
+                    }
+                    *d += sum as f64 / n as f64;
+                }
+            }
+        }
+
+        let n_scales = radii.len() as f64;
+        for row in &mut self.density {
+            for d in row {
+                *d /= n_scales;
+            }
+        }
+    }
+}
@@ -0,0 +1,872 @@
+use hlt::constants::Constants;
+use hlt::direction::Direction;
+use hlt::input::Input;
+use hlt::log::Log;
+use hlt::position::Position;
+use rng::Rng;
+use serde_json::{self, Map, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write as FmtWrite;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command as Process, Stdio};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use BotOptions;
+
+/// Parameters of a local game.
+#[derive(Debug, Copy, Clone, Serialize)]
+pub struct GameSettings {
+    /// Width and height of the map.
+    pub size: usize,
+    /// 2 or 4, like on the ladder.
+    pub players: usize,
+    pub seed: u64,
+}
+
+impl GameSettings {
+    /// Same rule as the official engine: 400 turns on 32x32 maps up to 500 turns on 64x64.
+    pub fn max_turns(&self) -> usize {
+        300 + 25 * self.size / 8
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.players != 2 && self.players != 4 {
+            return Err(format!("games need 2 or 4 players, not {}", self.players));
+        }
+        if self.size < 8 || !self.size.is_multiple_of(2) {
+            return Err(format!(
+                "map size must be even and at least 8, not {}",
+                self.size
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Constants sent to the bots, with the values of the official engine.
+fn constants(settings: &GameSettings) -> Map<String, Value> {
+    let pairs: Vec<(&str, Value)> = vec![
+        ("CAPTURE_ENABLED", false.into()),
+        ("CAPTURE_RADIUS", 3.into()),
+        ("DEFAULT_MAP_HEIGHT", settings.size.into()),
+        ("DEFAULT_MAP_WIDTH", settings.size.into()),
+        ("DROPOFF_COST", 4000.into()),
+        ("DROPOFF_PENALTY_RATIO", 4.into()),
+        ("EXTRACT_RATIO", 4.into()),
+        ("FACTOR_EXP_1", 2.0.into()),
+        ("FACTOR_EXP_2", 2.0.into()),
+        ("INITIAL_ENERGY", 5000.into()),
+        ("INSPIRATION_ENABLED", true.into()),
+        ("INSPIRATION_RADIUS", 4.into()),
+        ("INSPIRATION_SHIP_COUNT", 2.into()),
+        ("INSPIRED_BONUS_MULTIPLIER", 2.0.into()),
+        ("INSPIRED_EXTRACT_RATIO", 4.into()),
+        ("INSPIRED_MOVE_COST_RATIO", 10.into()),
+        ("MAX_CELL_PRODUCTION", 1000.into()),
+        ("MAX_ENERGY", 1000.into()),
+        ("MAX_PLAYERS", 16.into()),
+        ("MAX_TURNS", settings.max_turns().into()),
+        ("MAX_TURN_THRESHOLD", 64.into()),
+        ("MIN_CELL_PRODUCTION", 900.into()),
+        ("MIN_TURNS", 400.into()),
+        ("MIN_TURN_THRESHOLD", 32.into()),
+        ("MOVE_COST_RATIO", 10.into()),
+        ("NEW_ENTITY_ENERGY_COST", 1000.into()),
+        ("PERSISTENCE", 0.7.into()),
+        ("SHIPS_ABOVE_FOR_CAPTURE", 3.into()),
+        ("STRICT_ERRORS", false.into()),
+        ("game_seed", settings.seed.into()),
+    ];
+    pairs.into_iter().map(|(k, v)| (k.to_string(), v)).collect()
+}
+
+/// Symmetric fractal value noise in `[0, 1]`. The map is generated for one tile (half the map
+/// in 2 player games, a quarter in 4 player games) and mirrored, so all players start with
+/// the same chances.
+fn generate_halite(
+    settings: &GameSettings,
+    raw: &Map<String, Value>,
+    rng: &mut Rng,
+) -> Vec<Vec<usize>> {
+    let get = |key: &str| raw[key].as_f64().unwrap();
+    let size = settings.size;
+    let (tw, th) = if settings.players == 4 {
+        (size / 2, size / 2)
+    } else {
+        (size / 2, size)
+    };
+
+    let mut tile = vec![vec![0.0; tw]; th];
+    let mut amplitude = 1.0;
+    let mut period = tw.max(th) as f64 / 2.0;
+    while period >= 1.0 {
+        let gw = (tw as f64 / period).ceil() as usize + 2;
+        let gh = (th as f64 / period).ceil() as usize + 2;
+        let grid: Vec<Vec<f64>> = (0..gh)
+            .map(|_| (0..gw).map(|_| rng.next_f64()).collect())
+            .collect();
+
+        let smooth = |t: f64| t * t * (3.0 - 2.0 * t);
+        for (y, row) in tile.iter_mut().enumerate() {
+            let fy = y as f64 / period;
+            let (iy, ty) = (fy.floor() as usize, smooth(fy.fract()));
+            for (x, v) in row.iter_mut().enumerate() {
+                let fx = x as f64 / period;
+                let (ix, tx) = (fx.floor() as usize, smooth(fx.fract()));
+                let top = grid[iy][ix] * (1.0 - tx) + grid[iy][ix + 1] * tx;
+                let bottom = grid[iy + 1][ix] * (1.0 - tx) + grid[iy + 1][ix + 1] * tx;
+                *v += amplitude * (top * (1.0 - ty) + bottom * ty);
+            }
+        }
+
+        amplitude *= get("PERSISTENCE");
+        period /= 2.0;
+    }
+
+    let lo = tile.iter().flatten().cloned().fold(f64::INFINITY, f64::min);
+    let hi = tile
+        .iter()
+        .flatten()
+        .cloned()
+        .fold(f64::NEG_INFINITY, f64::max);
+    let min_peak = get("MIN_CELL_PRODUCTION");
+    let max_peak = get("MAX_CELL_PRODUCTION");
+    let peak = min_peak + rng.next_f64() * (max_peak - min_peak);
+    let exponent = get("FACTOR_EXP_1");
+
+    (0..size)
+        .map(|y| {
+            (0..size)
+                .map(|x| {
+                    let tx = if x < tw { x } else { size - 1 - x };
+                    let ty = if y < th { y } else { size - 1 - y };
+                    let v = (tile[ty][tx] - lo) / (hi - lo).max(1e-9);
+                    (v.powf(exponent) * peak) as usize
+                })
+                .collect()
+        })
+        .collect()
+}
+
+struct EngineShip {
+    owner: usize,
+    position: Position,
+    halite: usize,
+}
+
+struct EnginePlayer {
+    shipyard: Position,
+    halite: usize,
+    dropoffs: Vec<(usize, Position)>,
+    invalid_orders: usize,
+}
+
+/// What a player sees at the start of a turn. Nothing is hidden in Halite, so the
+/// observations of the players differ only in `player`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Observation {
+    pub player: usize,
+    pub turn: usize,
+    pub max_turns: usize,
+    /// Halite on the map, indexed `[y][x]`.
+    pub halite: Vec<Vec<usize>>,
+    pub players: Vec<PlayerObservation>,
+    pub ships: Vec<ShipObservation>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlayerObservation {
+    pub halite: usize,
+    pub shipyard: Position,
+    pub dropoffs: Vec<Position>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ShipObservation {
+    pub id: usize,
+    pub owner: usize,
+    pub position: Position,
+    pub halite: usize,
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Order {
+    Spawn,
+    Construct(usize),
+    Move(usize, Direction),
+}
+
+/// A local implementation of the Halite III rules.
+///
+/// It speaks the same text protocol as the official engine, so any bot can play in it. The
+/// rules follow the official engine closely, but not exactly: invalid orders are counted and
+/// ignored rather than disqualifying the bot, and capture is not implemented.
+pub struct Engine {
+    settings: GameSettings,
+    constants: Constants,
+    constants_json: String,
+    cells: Vec<Vec<usize>>,
+    /// Owner of the shipyard or dropoff at a position.
+    structures: HashMap<Position, usize>,
+    players: Vec<EnginePlayer>,
+    ships: BTreeMap<usize, EngineShip>,
+    next_ship: usize,
+    next_dropoff: usize,
+    turn: usize,
+    /// Cells whose halite changed in the last turn.
+    changed: HashSet<Position>,
+}
+
+impl Engine {
+    pub fn new(settings: GameSettings) -> Self {
+        let raw = constants(&settings);
+        let constants_json = serde_json::to_string(&raw).expect("constants serialize");
+        let constants = Constants::new(&constants_json).expect("valid constants");
+
+        let mut rng = Rng::new(settings.seed);
+        let mut cells = generate_halite(&settings, &raw, &mut rng);
+
+        let size = settings.size as i32;
+        let (qx, qy) = (
+            size / 4,
+            if settings.players == 4 {
+                size / 4
+            } else {
+                size / 2
+            },
+        );
+        let mut shipyards = vec![
+            Position { x: qx, y: qy },
+            Position {
+                x: size - 1 - qx,
+                y: qy,
+            },
+        ];
+        if settings.players == 4 {
+            shipyards.push(Position {
+                x: qx,
+                y: size - 1 - qy,
+            });
+            shipyards.push(Position {
+                x: size - 1 - qx,
+                y: size - 1 - qy,
+            });
+        }
+
+        let mut structures = HashMap::new();
+        let players = shipyards
+            .into_iter()
+            .enumerate()
+            .map(|(id, shipyard)| {
+                cells[shipyard.y as usize][shipyard.x as usize] = 0;
+                structures.insert(shipyard, id);
+                EnginePlayer {
+                    shipyard,
+                    halite: constants.initial_halite,
+                    dropoffs: vec![],
+                    invalid_orders: 0,
+                }
+            })
+            .collect();
+
+        Engine {
+            settings,
+            constants,
+            constants_json,
+            cells,
+            structures,
+            players,
+            ships: BTreeMap::new(),
+            next_ship: 0,
+            next_dropoff: 0,
+            turn: 1,
+            changed: HashSet::new(),
+        }
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.turn > self.constants.max_turns
+    }
+
+    pub fn halite(&self, player: usize) -> usize {
+        self.players[player].halite
+    }
+
+    /// Orders of a player that were ignored because they were malformed or not allowed.
+    pub fn invalid_orders(&self, player: usize) -> usize {
+        self.players[player].invalid_orders
+    }
+
+    /// The game header for one player.
+    pub fn header(&self, player: usize) -> String {
+        let mut out = String::new();
+        writeln!(out, "{}", self.constants_json).unwrap();
+        writeln!(out, "{} {}", self.players.len(), player).unwrap();
+        for (id, p) in self.players.iter().enumerate() {
+            writeln!(out, "{} {} {}", id, p.shipyard.x, p.shipyard.y).unwrap();
+        }
+        writeln!(out, "{} {}", self.settings.size, self.settings.size).unwrap();
+        for row in &self.cells {
+            let row: Vec<_> = row.iter().map(usize::to_string).collect();
+            writeln!(out, "{}", row.join(" ")).unwrap();
+        }
+        out
+    }
+
+    /// The frame all players receive at the start of the current turn.
+    pub fn frame(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "{}", self.turn).unwrap();
+        for (id, p) in self.players.iter().enumerate() {
+            let ships: Vec<_> = self.ships.iter().filter(|(_, s)| s.owner == id).collect();
+            writeln!(
+                out,
+                "{} {} {} {}",
+                id,
+                ships.len(),
+                p.dropoffs.len(),
+                p.halite
+            )
+            .unwrap();
+            for (sid, s) in ships {
+                writeln!(
+                    out,
+                    "{} {} {} {}",
+                    sid, s.position.x, s.position.y, s.halite
+                )
+                .unwrap();
+            }
+            for (did, pos) in &p.dropoffs {
+                writeln!(out, "{} {} {}", did, pos.x, pos.y).unwrap();
+            }
+        }
+        writeln!(out, "{}", self.changed.len()).unwrap();
+        for pos in &self.changed {
+            let halite = self.cells[pos.y as usize][pos.x as usize];
+            writeln!(out, "{} {} {}", pos.x, pos.y, halite).unwrap();
+        }
+        out
+    }
+
+    /// The state at the start of the current turn, as seen by `player`.
+    pub fn observe(&self, player: usize) -> Observation {
+        Observation {
+            player,
+            turn: self.turn,
+            max_turns: self.constants.max_turns,
+            halite: self.cells.clone(),
+            players: self
+                .players
+                .iter()
+                .map(|p| PlayerObservation {
+                    halite: p.halite,
+                    shipyard: p.shipyard,
+                    dropoffs: p.dropoffs.iter().map(|&(_, pos)| pos).collect(),
+                })
+                .collect(),
+            ships: self
+                .ships
+                .iter()
+                .map(|(&id, s)| ShipObservation {
+                    id,
+                    owner: s.owner,
+                    position: s.position,
+                    halite: s.halite,
+                })
+                .collect(),
+        }
+    }
+
+    /// Decode a player's command line. Returns the valid orders and the number of invalid ones,
+    /// which are dropped.
+    fn parse_orders(&self, player: usize, line: &str) -> (Vec<Order>, usize) {
+        let mut orders = vec![];
+        let mut invalid = 0;
+        let mut commanded = HashSet::new();
+        let mut spawned = false;
+        let owns = |id: usize| matches!(self.ships.get(&id), Some(s) if s.owner == player);
+
+        let mut tokens = line.split_whitespace();
+        while let Some(token) = tokens.next() {
+            let order = match token {
+                "g" if !spawned => {
+                    spawned = true;
+                    Some(Order::Spawn)
+                }
+                "c" => tokens
+                    .next()
+                    .and_then(|t| t.parse().ok())
+                    .map(Order::Construct),
+                "m" => {
+                    let id = tokens.next().and_then(|t| t.parse().ok());
+                    let d = tokens
+                        .next()
+                        .and_then(|t| t.chars().next())
+                        .and_then(Direction::from_char_encoding);
+                    match (id, d) {
+                        (Some(id), Some(d)) => Some(Order::Move(id, d)),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+
+            match order {
+                Some(Order::Construct(id)) | Some(Order::Move(id, _))
+                    if !owns(id) || !commanded.insert(id) =>
+                {
+                    invalid += 1
+                }
+                Some(order) => orders.push(order),
+                None => invalid += 1,
+            }
+        }
+        (orders, invalid)
+    }
+
+    fn normalize(&self, pos: Position) -> Position {
+        let size = self.settings.size as i32;
+        Position {
+            x: pos.x.rem_euclid(size),
+            y: pos.y.rem_euclid(size),
+        }
+    }
+
+    fn cell(&mut self, pos: Position) -> &mut usize {
+        &mut self.cells[pos.y as usize][pos.x as usize]
+    }
+
+    fn is_inspired(&self, ship: &EngineShip) -> bool {
+        let c = &self.constants;
+        let size = self.settings.size as i32;
+        let dist = |a: Position, b: Position| {
+            let dx = (a.x - b.x).abs();
+            let dy = (a.y - b.y).abs();
+            (dx.min(size - dx) + dy.min(size - dy)) as usize
+        };
+        c.inspiration_enabled
+            && self
+                .ships
+                .values()
+                .filter(|other| other.owner != ship.owner)
+                .filter(|other| dist(ship.position, other.position) <= c.inspiration_radius)
+                .count()
+                >= c.inspiration_ship_count
+    }
+
+    /// Apply one command line per player and advance to the next turn.
+    pub fn step(&mut self, commands: &[String]) {
+        self.changed.clear();
+
+        let mut orders = vec![];
+        for (player, line) in commands.iter().enumerate() {
+            let (valid, invalid) = self.parse_orders(player, line);
+            orders.extend(valid.into_iter().map(|o| (player, o)));
+            self.players[player].invalid_orders += invalid;
+        }
+
+        let inspired: HashSet<usize> = self
+            .ships
+            .iter()
+            .filter(|(_, s)| self.is_inspired(s))
+            .map(|(&id, _)| id)
+            .collect();
+
+        // dropoffs; the ship's cargo and the halite on the cell count towards the cost
+        for &(player, order) in &orders {
+            if let Order::Construct(id) = order {
+                let pos = self.ships[&id].position;
+                if self.structures.contains_key(&pos) {
+                    continue;
+                }
+                let funds = self.players[player].halite + self.ships[&id].halite + *self.cell(pos);
+                if funds < self.constants.dropoff_cost {
+                    continue;
+                }
+                self.players[player].halite = funds - self.constants.dropoff_cost;
+                *self.cell(pos) = 0;
+                self.changed.insert(pos);
+                self.structures.insert(pos, player);
+                self.players[player].dropoffs.push((self.next_dropoff, pos));
+                self.next_dropoff += 1;
+                self.ships.remove(&id);
+            }
+        }
+
+        // movement and mining
+        let moves: HashMap<usize, Direction> = orders
+            .iter()
+            .filter_map(|&(_, o)| match o {
+                Order::Move(id, d) => Some((id, d)),
+                _ => None,
+            })
+            .collect();
+        let ids: Vec<usize> = self.ships.keys().cloned().collect();
+        for id in ids {
+            let pos = self.ships[&id].position;
+            let cell = *self.cell(pos);
+            let inspired = inspired.contains(&id);
+            let c = self.constants.clone();
+            match moves.get(&id) {
+                Some(&d) if d != Direction::Still => {
+                    let ratio = if inspired {
+                        c.inspired_move_cost_ratio
+                    } else {
+                        c.move_cost_ratio
+                    };
+                    let target = self.normalize(pos.directional_offset(d));
+                    let ship = self.ships.get_mut(&id).unwrap();
+                    if ship.halite >= cell / ratio {
+                        ship.halite -= cell / ratio;
+                        ship.position = target;
+                    }
+                }
+                _ => {
+                    let ratio = if inspired {
+                        c.inspired_extract_ratio
+                    } else {
+                        c.extract_ratio
+                    };
+                    let capacity = c.max_halite - self.ships[&id].halite;
+                    let mined = cell.div_ceil(ratio).min(capacity);
+                    let bonus = if inspired {
+                        ((mined as f64 * c.inspired_bonus_multiplier) as usize)
+                            .min(capacity - mined)
+                    } else {
+                        0
+                    };
+                    if mined > 0 {
+                        *self.cell(pos) -= mined;
+                        self.changed.insert(pos);
+                    }
+                    self.ships.get_mut(&id).unwrap().halite += mined + bonus;
+                }
+            }
+        }
+
+        // new ships appear on the shipyard after movement, so they collide with ships that
+        // stay or arrive there
+        for &(player, order) in &orders {
+            if let Order::Spawn = order {
+                if self.players[player].halite >= self.constants.ship_cost {
+                    self.players[player].halite -= self.constants.ship_cost;
+                    let ship = EngineShip {
+                        owner: player,
+                        position: self.players[player].shipyard,
+                        halite: 0,
+                    };
+                    self.ships.insert(self.next_ship, ship);
+                    self.next_ship += 1;
+                }
+            }
+        }
+
+        // collisions; the cargo of all ships involved drops to the cell or, on a structure,
+        // goes to its owner
+        let mut occupants: HashMap<Position, Vec<usize>> = HashMap::new();
+        for (&id, ship) in &self.ships {
+            occupants.entry(ship.position).or_default().push(id);
+        }
+        for (pos, ids) in occupants {
+            if ids.len() < 2 {
+                continue;
+            }
+            let cargo: usize = ids
+                .iter()
+                .map(|id| self.ships.remove(id).unwrap().halite)
+                .sum();
+            match self.structures.get(&pos) {
+                Some(&owner) => self.players[owner].halite += cargo,
+                None => {
+                    *self.cell(pos) += cargo;
+                    self.changed.insert(pos);
+                }
+            }
+        }
+
+        // deliveries
+        for ship in self.ships.values_mut() {
+            if self.structures.get(&ship.position) == Some(&ship.owner) {
+                self.players[ship.owner].halite += ship.halite;
+                ship.halite = 0;
+            }
+        }
+
+        self.turn += 1;
+    }
+
+    /// Ranks by halite, 1 for the winner. Ties go to the lower player id.
+    pub fn ranks(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.players.len()).collect();
+        order.sort_by_key(|&p| std::cmp::Reverse(self.players[p].halite));
+        let mut ranks = vec![0; order.len()];
+        for (rank, p) in order.into_iter().enumerate() {
+            ranks[p] = rank + 1;
+        }
+        ranks
+    }
+}
+
+/// A participant in a local game.
+pub trait Bot {
+    /// Send the game header and return the bot's name.
+    fn start(&mut self, header: &str) -> io::Result<String>;
+
+    /// Send a frame and return the bot's command line.
+    fn turn(&mut self, frame: &str) -> io::Result<String>;
+}
+
+/// Runs a bot executable, e.g. an old submission.
+pub struct ProcessBot {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl ProcessBot {
+    /// Start `command` in a shell. The bot's log files end up in the working directory.
+    pub fn spawn(command: &str) -> io::Result<Self> {
+        let mut child = Process::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdin = child.stdin.take();
+        let stdout = BufReader::new(child.stdout.take().expect("piped stdout"));
+        Ok(ProcessBot {
+            child,
+            stdin,
+            stdout,
+        })
+    }
+
+    fn exchange(&mut self, message: &str) -> io::Result<String> {
+        match self.stdin {
+            Some(ref mut stdin) => stdin.write_all(message.as_bytes())?,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::BrokenPipe,
+                    "bot input closed",
+                ))
+            }
+        }
+        let mut line = String::new();
+        if self.stdout.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "bot closed its output",
+            ));
+        }
+        Ok(line)
+    }
+}
+
+impl Bot for ProcessBot {
+    fn start(&mut self, header: &str) -> io::Result<String> {
+        self.exchange(header).map(|name| name.trim().to_string())
+    }
+
+    fn turn(&mut self, frame: &str) -> io::Result<String> {
+        self.exchange(frame)
+    }
+}
+
+impl Drop for ProcessBot {
+    fn drop(&mut self) {
+        // closing its input tells the bot that the game is over
+        self.stdin = None;
+        let _ = self.child.wait();
+    }
+}
+
+/// Feeds the messages from a channel to a bot's `Input`.
+pub struct ChannelReader {
+    rx: Receiver<String>,
+    pending: Vec<u8>,
+    pos: usize,
+}
+
+impl ChannelReader {
+    pub fn new(rx: Receiver<String>) -> Self {
+        ChannelReader {
+            rx,
+            pending: vec![],
+            pos: 0,
+        }
+    }
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.pending.len() {
+            match self.rx.recv() {
+                Ok(message) => {
+                    self.pending = message.into_bytes();
+                    self.pos = 0;
+                }
+                Err(_) => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.pending.len() - self.pos);
+        buf[..n].copy_from_slice(&self.pending[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Sends every line a bot writes through a channel.
+pub struct ChannelWriter {
+    tx: Sender<String>,
+    line: Vec<u8>,
+}
+
+impl ChannelWriter {
+    pub fn new(tx: Sender<String>) -> Self {
+        ChannelWriter { tx, line: vec![] }
+    }
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &b in buf {
+            if b == b'\n' {
+                let line = String::from_utf8_lossy(&self.line).into_owned();
+                self.line.clear();
+                self.tx
+                    .send(line)
+                    .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "engine is gone"))?;
+            } else {
+                self.line.push(b);
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Runs this bot in a thread of the current process, which is much cheaper than starting
+/// executables for every game.
+pub struct LocalBot {
+    to_bot: Option<Sender<String>>,
+    from_bot: Receiver<String>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl LocalBot {
+    pub fn spawn(options: BotOptions) -> Self {
+        let (to_bot, bot_input) = channel();
+        let (bot_output, from_bot) = channel();
+        let thread = thread::spawn(move || {
+            let reader = ChannelReader::new(bot_input);
+            let input = Input::new(Box::new(BufReader::new(reader)));
+            let output = Box::new(ChannelWriter::new(bot_output));
+            if let Err(e) = ::run_bot(input, output, &options) {
+                Log::log(&format!("Error: {}", e));
+            }
+        });
+        LocalBot {
+            to_bot: Some(to_bot),
+            from_bot,
+            thread: Some(thread),
+        }
+    }
+
+    fn exchange(&mut self, message: &str) -> io::Result<String> {
+        let gone = || io::Error::new(io::ErrorKind::BrokenPipe, "bot thread stopped");
+        let to_bot = self.to_bot.as_ref().ok_or_else(gone)?;
+        to_bot.send(message.to_string()).map_err(|_| gone())?;
+        self.from_bot.recv().map_err(|_| gone())
+    }
+}
+
+impl Bot for LocalBot {
+    fn start(&mut self, header: &str) -> io::Result<String> {
+        self.exchange(header)
+    }
+
+    fn turn(&mut self, frame: &str) -> io::Result<String> {
+        self.exchange(frame)
+    }
+}
+
+impl Drop for LocalBot {
+    fn drop(&mut self) {
+        self.to_bot = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlayerResult {
+    pub name: String,
+    pub halite: usize,
+    pub rank: usize,
+    /// The bot stopped responding during the game.
+    pub crashed: bool,
+    pub invalid_orders: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GameResult {
+    pub settings: GameSettings,
+    pub players: Vec<PlayerResult>,
+}
+
+/// Play a full game. `bots[i]` plays as player `i`.
+pub fn play_game(settings: GameSettings, bots: &mut [Box<dyn Bot>]) -> GameResult {
+    let mut engine = Engine::new(settings);
+    let mut crashed = vec![false; bots.len()];
+
+    let names: Vec<String> = bots
+        .iter_mut()
+        .enumerate()
+        .map(|(id, bot)| match bot.start(&engine.header(id)) {
+            Ok(name) => name,
+            Err(_) => {
+                crashed[id] = true;
+                format!("player {}", id)
+            }
+        })
+        .collect();
+
+    while !engine.is_over() {
+        let frame = engine.frame();
+        let commands: Vec<String> = bots
+            .iter_mut()
+            .enumerate()
+            .map(|(id, bot)| {
+                if crashed[id] {
+                    return String::new();
+                }
+                bot.turn(&frame).unwrap_or_else(|_| {
+                    crashed[id] = true;
+                    String::new()
+                })
+            })
+            .collect();
+        engine.step(&commands);
+    }
+
+    let ranks = engine.ranks();
+    GameResult {
+        settings,
+        players: names
+            .into_iter()
+            .enumerate()
+            .map(|(id, name)| PlayerResult {
+                name,
+                halite: engine.halite(id),
+                rank: ranks[id],
+                crashed: crashed[id],
+                invalid_orders: engine.invalid_orders(id),
+            })
+            .collect(),
+    }
+}
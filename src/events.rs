@@ -0,0 +1,217 @@
+use hlt::game::Game;
+use hlt::log::Log;
+use hlt::position::Position;
+use hlt::ship::Ship;
+use hlt::DropoffId;
+use hlt::PlayerId;
+use hlt::ShipId;
+use std::collections::{HashMap, HashSet};
+
+/// Something that happened between the previous frame and the current one.
+#[derive(Debug, Clone, Serialize)]
+pub enum GameEvent {
+    ShipSpawned {
+        id: ShipId,
+        owner: PlayerId,
+        position: Position,
+    },
+
+    /// A ship disappeared without turning into a dropoff. `collided_with` lists the other ships
+    /// that disappeared close enough to have moved onto the same cell, and `location` is the
+    /// most likely cell of the collision, if it can be determined.
+    ShipDestroyed {
+        id: ShipId,
+        owner: PlayerId,
+        last_position: Position,
+        cargo: usize,
+        collided_with: Vec<ShipId>,
+        location: Option<Position>,
+    },
+
+    DropoffCreated {
+        id: DropoffId,
+        owner: PlayerId,
+        position: Position,
+        builder: Option<ShipId>,
+    },
+
+    /// Halite appeared on a cell; this only happens when ships sink.
+    HaliteDropped { position: Position, amount: usize },
+
+    /// An enemy ship sits on our shipyard for at least the second turn in a row.
+    ShipyardBlocked {
+        id: ShipId,
+        owner: PlayerId,
+        turns: usize,
+    },
+}
+
+/// Derives game events by comparing consecutive frames.
+pub struct EventExtractor {
+    prev_ships: HashMap<ShipId, Ship>,
+    prev_halite: Vec<Vec<usize>>,
+    known_dropoffs: HashSet<DropoffId>,
+    shipyard_blocked: Option<(ShipId, usize)>,
+}
+
+impl EventExtractor {
+    pub fn new(game: &Game) -> Self {
+        EventExtractor {
+            prev_ships: HashMap::new(),
+            prev_halite: game
+                .map
+                .cells
+                .iter()
+                .map(|row| row.iter().map(|cell| cell.halite).collect())
+                .collect(),
+            known_dropoffs: HashSet::new(),
+            shipyard_blocked: None,
+        }
+    }
+
+    pub fn update_frame(&mut self, game: &Game) -> Vec<GameEvent> {
+        let mut events = vec![];
+
+        let mut spawned: Vec<_> = game
+            .ships
+            .values()
+            .filter(|ship| !self.prev_ships.contains_key(&ship.id))
+            .collect();
+        spawned.sort_unstable_by_key(|ship| ship.id.0);
+        for ship in spawned {
+            events.push(GameEvent::ShipSpawned {
+                id: ship.id,
+                owner: ship.owner,
+                position: ship.position,
+            });
+        }
+
+        let mut dropped = vec![];
+        for (i, row) in game.map.cells.iter().enumerate() {
+            for (j, cell) in row.iter().enumerate() {
+                let prev = self.prev_halite[i][j];
+                if cell.halite > prev {
+                    dropped.push((cell.position, cell.halite - prev));
+                }
+            }
+        }
+
+        let mut new_dropoffs: Vec<_> = game
+            .dropoffs
+            .values()
+            .filter(|d| !self.known_dropoffs.contains(&d.id))
+            .collect();
+        new_dropoffs.sort_unstable_by_key(|d| d.id.0);
+
+        let mut vanished: Vec<_> = self
+            .prev_ships
+            .values()
+            .filter(|ship| !game.ships.contains_key(&ship.id))
+            .collect();
+        vanished.sort_unstable_by_key(|ship| ship.id.0);
+
+        let mut builders = HashSet::new();
+        for dropoff in &new_dropoffs {
+            let builder = vanished
+                .iter()
+                .find(|ship| ship.owner == dropoff.owner && ship.position == dropoff.position)
+                .map(|ship| ship.id);
+            builders.extend(builder);
+            events.push(GameEvent::DropoffCreated {
+                id: dropoff.id,
+                owner: dropoff.owner,
+                position: dropoff.position,
+                builder,
+            });
+        }
+
+        let destroyed: Vec<_> = vanished
+            .into_iter()
+            .filter(|ship| !builders.contains(&ship.id))
+            .collect();
+
+        for ship in &destroyed {
+            let neighborhood = |pos: &Position| {
+                let mut cells = pos.get_surrounding_cardinals();
+                cells.push(*pos);
+                cells
+                    .into_iter()
+                    .map(|p| game.map.normalize(&p))
+                    .collect::<HashSet<_>>()
+            };
+            let reachable = neighborhood(&ship.position);
+
+            let partners: Vec<_> = destroyed
+                .iter()
+                .filter(|other| other.id != ship.id)
+                .filter(|other| !reachable.is_disjoint(&neighborhood(&other.position)))
+                .collect();
+
+            let mut candidates = reachable.clone();
+            for other in &partners {
+                candidates = &candidates & &neighborhood(&other.position);
+            }
+
+            let location = dropped
+                .iter()
+                .map(|&(pos, _)| pos)
+                .find(|pos| candidates.contains(pos))
+                .or_else(|| {
+                    if candidates.len() == 1 {
+                        candidates.iter().next().cloned()
+                    } else {
+                        None
+                    }
+                });
+
+            events.push(GameEvent::ShipDestroyed {
+                id: ship.id,
+                owner: ship.owner,
+                last_position: ship.position,
+                cargo: ship.halite,
+                collided_with: partners.iter().map(|other| other.id).collect(),
+                location,
+            });
+        }
+
+        for (position, amount) in dropped {
+            events.push(GameEvent::HaliteDropped { position, amount });
+        }
+
+        let shipyard = game.players[game.my_id.0].shipyard.position;
+        let blocker = game
+            .ships
+            .values()
+            .find(|ship| ship.owner != game.my_id && ship.position == shipyard);
+        self.shipyard_blocked = match (blocker, self.shipyard_blocked) {
+            (Some(ship), Some((id, turns))) if ship.id == id => {
+                events.push(GameEvent::ShipyardBlocked {
+                    id,
+                    owner: ship.owner,
+                    turns: turns + 1,
+                });
+                Some((id, turns + 1))
+            }
+            (Some(ship), _) => Some((ship.id, 1)),
+            (None, _) => None,
+        };
+
+        self.prev_ships = game.ships.clone();
+        for (i, row) in game.map.cells.iter().enumerate() {
+            for (j, cell) in row.iter().enumerate() {
+                self.prev_halite[i][j] = cell.halite;
+            }
+        }
+        self.known_dropoffs
+            .extend(new_dropoffs.iter().map(|d| d.id));
+
+        for event in &events {
+            match event {
+                GameEvent::ShipSpawned { .. } | GameEvent::HaliteDropped { .. } => {}
+                _ => Log::log(&format!("Event: {:?}", event)),
+            }
+        }
+
+        events
+    }
+}
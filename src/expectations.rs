@@ -0,0 +1,326 @@
+use events::GameEvent;
+use hlt::command::Command;
+use hlt::direction::Direction;
+use hlt::game::Game;
+use hlt::log::Log;
+use hlt::map_cell::Structure;
+use hlt::position::Position;
+use hlt::ShipId;
+use std::collections::HashMap;
+
+/// A command we sent, decoded from its wire format.
+#[derive(Debug, Copy, Clone)]
+enum Order {
+    Spawn,
+    Move(ShipId, Direction),
+    Dropoff(ShipId),
+}
+
+impl Order {
+    fn parse(cmd: &Command) -> Option<Order> {
+        let mut tokens = cmd.0.split_whitespace();
+        match tokens.next()? {
+            "g" => Some(Order::Spawn),
+            "c" => Some(Order::Dropoff(ShipId(tokens.next()?.parse().ok()?))),
+            "m" => {
+                let id = ShipId(tokens.next()?.parse().ok()?);
+                let d = Direction::from_char_encoding(tokens.next()?.chars().next()?)?;
+                Some(Order::Move(id, d))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Where we expect one of our ships in the next frame.
+#[derive(Debug, Copy, Clone, Serialize)]
+struct ShipPrediction {
+    position: Position,
+    /// `None` if the cargo cannot be predicted, e.g. for moves we cannot afford.
+    cargo: Option<usize>,
+}
+
+/// A difference between the predicted and the actual frame.
+#[derive(Debug, Clone, Serialize)]
+pub enum Discrepancy {
+    /// The ship did not end up where we sent it.
+    WrongPosition {
+        id: ShipId,
+        expected: Position,
+        found: Position,
+    },
+    WrongCargo {
+        id: ShipId,
+        expected: usize,
+        found: usize,
+    },
+    /// The ship was destroyed although we did not expect a collision.
+    UnexpectedCollision {
+        id: ShipId,
+        collided_with: Vec<ShipId>,
+    },
+    /// The ship disappeared without a trace.
+    ShipMissing {
+        id: ShipId,
+    },
+    DropoffRejected {
+        id: ShipId,
+        position: Position,
+    },
+    SpawnRejected,
+    WrongHalite {
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl Discrepancy {
+    /// The ship the discrepancy is about, if any.
+    pub fn ship(&self) -> Option<ShipId> {
+        match *self {
+            Discrepancy::WrongPosition { id, .. }
+            | Discrepancy::WrongCargo { id, .. }
+            | Discrepancy::UnexpectedCollision { id, .. }
+            | Discrepancy::ShipMissing { id }
+            | Discrepancy::DropoffRejected { id, .. } => Some(id),
+            Discrepancy::SpawnRejected | Discrepancy::WrongHalite { .. } => None,
+        }
+    }
+}
+
+/// Predicts the effect of our commands on the next frame and compares the prediction with
+/// what the server reports.
+///
+/// The prediction only covers our own entities, and assumes no interference by the opponents.
+/// Everything the opponents do to us therefore shows up as a discrepancy, as do commands the
+/// server rejected and mistakes in our own cost calculations.
+#[derive(Serialize)]
+pub struct Expectations {
+    ships: HashMap<ShipId, ShipPrediction>,
+    dropoffs: Vec<(ShipId, Position)>,
+    spawn: bool,
+    halite: Option<usize>,
+
+    discrepancies: Vec<Discrepancy>,
+}
+
+impl Expectations {
+    pub fn new() -> Self {
+        Expectations {
+            ships: HashMap::new(),
+            dropoffs: vec![],
+            spawn: false,
+            halite: None,
+            discrepancies: vec![],
+        }
+    }
+
+    /// Predict the next frame from the current one and the commands issued this turn.
+    pub fn predict(&mut self, game: &Game, commands: &[Command]) {
+        let me = &game.players[game.my_id.0];
+        let consts = &game.constants;
+
+        let mut orders = HashMap::new();
+        let mut halite = me.halite as i64;
+        self.spawn = false;
+        self.dropoffs.clear();
+        self.ships.clear();
+
+        for cmd in commands {
+            match Order::parse(cmd) {
+                Some(Order::Spawn) => {
+                    self.spawn = true;
+                    halite -= consts.ship_cost as i64;
+                }
+                Some(Order::Move(id, d)) => {
+                    orders.insert(id, d);
+                }
+                Some(Order::Dropoff(id)) => {
+                    let ship = &game.ships[&id];
+                    let cell = game.map.at_position(&ship.position).halite;
+                    halite += (ship.halite + cell) as i64 - consts.dropoff_cost as i64;
+                    self.dropoffs.push((id, ship.position));
+                }
+                None => Log::log(&format!("Expectations: cannot decode command {:?}", cmd)),
+            }
+        }
+
+        let is_my_structure = |pos: &Position| match game.map.at_position(pos).structure {
+            Structure::Shipyard(pid) => pid == game.my_id,
+            Structure::Dropoff(did) => game.dropoffs[&did].owner == game.my_id,
+            Structure::None => false,
+        };
+
+        for id in &me.ship_ids {
+            if self.dropoffs.iter().any(|&(b, _)| b == *id) {
+                continue;
+            }
+
+            let ship = &game.ships[id];
+            let cell = game.map.at_position(&ship.position).halite;
+            let inspired = consts.inspiration_enabled
+                && game
+                    .ships
+                    .values()
+                    .filter(|other| other.owner != game.my_id)
+                    .filter(|other| {
+                        game.map.calculate_distance(&ship.position, &other.position)
+                            <= consts.inspiration_radius
+                    })
+                    .count()
+                    >= consts.inspiration_ship_count;
+
+            let d = orders.get(id).cloned().unwrap_or(Direction::Still);
+            let move_cost_ratio = if inspired {
+                consts.inspired_move_cost_ratio
+            } else {
+                consts.move_cost_ratio
+            };
+
+            let prediction = if d == Direction::Still {
+                let ratio = if inspired {
+                    consts.inspired_extract_ratio
+                } else {
+                    consts.extract_ratio
+                };
+                let mut mined = ((cell as f64 / ratio as f64).ceil() as usize).min(ship.capacity());
+                if inspired {
+                    let bonus = (mined as f64 * consts.inspired_bonus_multiplier) as usize;
+                    mined = (mined + bonus).min(ship.capacity());
+                }
+                ShipPrediction {
+                    position: ship.position,
+                    cargo: Some(ship.halite + mined),
+                }
+            } else if ship.halite < cell / move_cost_ratio {
+                ShipPrediction {
+                    position: ship.position,
+                    cargo: None,
+                }
+            } else {
+                let target = game.map.normalize(&ship.position.directional_offset(d));
+                let cargo = ship.halite - cell / move_cost_ratio;
+                if is_my_structure(&target) {
+                    halite += cargo as i64;
+                    ShipPrediction {
+                        position: target,
+                        cargo: Some(0),
+                    }
+                } else {
+                    ShipPrediction {
+                        position: target,
+                        cargo: Some(cargo),
+                    }
+                }
+            };
+
+            self.ships.insert(*id, prediction);
+        }
+
+        self.halite = if halite >= 0 {
+            Some(halite as usize)
+        } else {
+            Log::log(&format!(
+                "Expectations: commands overspend our halite by {}",
+                -halite
+            ));
+            None
+        };
+    }
+
+    /// Compare the new frame with the last prediction.
+    pub fn check(&mut self, game: &Game, events: &[GameEvent]) {
+        self.discrepancies.clear();
+
+        for (&id, prediction) in &self.ships {
+            let ship = match game.ships.get(&id) {
+                Some(ship) => ship,
+                None => {
+                    let collision = events.iter().find_map(|event| match *event {
+                        GameEvent::ShipDestroyed {
+                            id: lost,
+                            ref collided_with,
+                            ..
+                        } if lost == id => Some(collided_with.clone()),
+                        _ => None,
+                    });
+                    self.discrepancies.push(match collision {
+                        Some(collided_with) => {
+                            Discrepancy::UnexpectedCollision { id, collided_with }
+                        }
+                        None => Discrepancy::ShipMissing { id },
+                    });
+                    continue;
+                }
+            };
+
+            if ship.position != prediction.position {
+                self.discrepancies.push(Discrepancy::WrongPosition {
+                    id,
+                    expected: prediction.position,
+                    found: ship.position,
+                });
+                continue;
+            }
+
+            match prediction.cargo {
+                Some(expected) if expected != ship.halite => {
+                    self.discrepancies.push(Discrepancy::WrongCargo {
+                        id,
+                        expected,
+                        found: ship.halite,
+                    })
+                }
+                _ => {}
+            }
+        }
+
+        for &(id, position) in &self.dropoffs {
+            let built = events.iter().any(|event| match *event {
+                GameEvent::DropoffCreated { builder, .. } => builder == Some(id),
+                _ => false,
+            });
+            if !built {
+                self.discrepancies
+                    .push(Discrepancy::DropoffRejected { id, position });
+            }
+        }
+
+        if self.spawn {
+            let shipyard = game.players[game.my_id.0].shipyard.position;
+            let spawned = events.iter().any(|event| match *event {
+                GameEvent::ShipSpawned {
+                    owner, position, ..
+                } => owner == game.my_id && position == shipyard,
+                _ => false,
+            });
+            if !spawned {
+                self.discrepancies.push(Discrepancy::SpawnRejected);
+            }
+        }
+
+        if let Some(expected) = self.halite {
+            let found = game.players[game.my_id.0].halite;
+            if found != expected {
+                self.discrepancies
+                    .push(Discrepancy::WrongHalite { expected, found });
+            }
+        }
+
+        for d in &self.discrepancies {
+            Log::log(&format!("Expectations: {:?}", d));
+        }
+    }
+
+    /// Everything that did not go as predicted last turn.
+    pub fn discrepancies(&self) -> &[Discrepancy] {
+        &self.discrepancies
+    }
+
+    /// Discrepancies concerning one ship.
+    pub fn for_ship(&self, id: ShipId) -> impl Iterator<Item = &Discrepancy> {
+        self.discrepancies
+            .iter()
+            .filter(move |d| d.ship() == Some(id))
+    }
+}
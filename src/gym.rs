@@ -0,0 +1,324 @@
+use ai_manager::AiManager;
+use engine::{Bot, ChannelReader, ChannelWriter, Engine, GameSettings, Observation};
+use hlt::command::Command;
+use hlt::direction::Direction;
+use hlt::error::ProtocolError;
+use hlt::input::Input;
+use hlt::log::Log;
+use hlt::ShipId;
+use rng::Rng;
+use serde_json;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use tournament::BotSpec;
+use {BotOptions, GameState};
+
+/// Who plays a seat in an `Env`.
+#[derive(Debug, Clone)]
+pub enum Seat {
+    /// All orders come from `Env::step`.
+    Agent,
+    /// This bot plays, but the ships given a move in `Env::step` follow it instead of their AI.
+    /// The rest of the fleet plans around them, so a single ship can be trained in a normal
+    /// game. A steered ship only waits if it would run into a ship being spawned.
+    Assisted(BotOptions),
+    /// Any other bot, which plays on its own.
+    Bot(BotSpec),
+}
+
+impl Seat {
+    /// `agent`, `assisted` for this bot with `opts`, or a bot as for `BotSpec::parse`.
+    pub fn parse(spec: &str, opts: &BotOptions) -> Result<Self, String> {
+        Ok(match spec {
+            "agent" => Seat::Agent,
+            "assisted" => Seat::Assisted(opts.clone()),
+            _ => Seat::Bot(BotSpec::parse(spec, opts)?),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum EnvError {
+    Settings(String),
+    /// A bot could not be started.
+    Bot(String, io::Error),
+    /// An assisted bot did not get through the game header.
+    Protocol(ProtocolError),
+    /// `step` without a running game.
+    NoGame,
+    /// A request to `serve` that is not valid JSON or not a known request.
+    Request(serde_json::Error),
+}
+
+impl fmt::Display for EnvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EnvError::Settings(e) => write!(f, "{}", e),
+            EnvError::Bot(label, e) => write!(f, "can't start '{}': {}", label, e),
+            EnvError::Protocol(e) => write!(f, "assisted bot failed: {}", e),
+            EnvError::NoGame => write!(f, "no game running, reset first"),
+            EnvError::Request(e) => write!(f, "malformed request: {}", e),
+        }
+    }
+}
+
+impl Error for EnvError {}
+
+/// Result of one turn.
+#[derive(Debug, Clone, Serialize)]
+pub struct Step {
+    pub observations: Vec<Observation>,
+    /// Change of each player's halite, so the rewards of a game add up to the final score
+    /// minus the initial halite. Spawning and building dropoffs show up as negative rewards.
+    pub rewards: Vec<f64>,
+    /// The game is over and `reset` must be called before the next step.
+    pub done: bool,
+}
+
+/// This bot, run turn by turn in the calling thread so its ships can be steered.
+struct AssistedBot {
+    world: GameState,
+    ai: AiManager,
+    to_bot: Sender<String>,
+    from_bot: Receiver<String>,
+    dump_file: Option<String>,
+}
+
+impl AssistedBot {
+    fn start(header: &str, opts: &BotOptions) -> Result<Self, ProtocolError> {
+        let (to_bot, bot_input) = channel();
+        let (bot_output, from_bot) = channel();
+        to_bot
+            .send(header.to_string())
+            .expect("the bot's input is alive");
+        let input = Input::new(Box::new(BufReader::new(ChannelReader::new(bot_input))));
+        let output = Box::new(ChannelWriter::new(bot_output));
+        let world = GameState::new(input, output, opts)?;
+        // the bot's name
+        let _ = from_bot.try_recv();
+        Ok(AssistedBot {
+            world,
+            ai: AiManager::new(),
+            to_bot,
+            from_bot,
+            dump_file: opts.dump_file.clone(),
+        })
+    }
+
+    fn turn(
+        &mut self,
+        frame: &str,
+        steered: HashMap<ShipId, Direction>,
+    ) -> Result<String, ProtocolError> {
+        self.to_bot
+            .send(frame.to_string())
+            .expect("the bot's input is alive");
+        self.world.update_frame()?;
+        self.ai.steered = steered;
+        self.ai.think(&mut self.world);
+        self.ai.steered.clear();
+        self.world
+            .finalize_frame(self.dump_file.as_ref().map(String::as_ref))?;
+        Ok(self.from_bot.try_recv().unwrap_or_default())
+    }
+}
+
+enum Player {
+    Agent,
+    Assisted(Box<AssistedBot>),
+    Bot(Box<dyn Bot>),
+    /// A bot that stopped responding; its ships stay still for the rest of the game.
+    Crashed,
+}
+
+/// Decode a move order, the only kind an assisted seat takes.
+fn parse_move(command: &Command) -> Option<(ShipId, Direction)> {
+    let mut tokens = command.0.split_whitespace();
+    match (tokens.next(), tokens.next(), tokens.next()) {
+        (Some("m"), Some(id), Some(d)) => {
+            let id = id.parse().ok()?;
+            let d = d.chars().next().and_then(Direction::from_char_encoding)?;
+            Some((ShipId(id), d))
+        }
+        _ => None,
+    }
+}
+
+/// A Gym-style interface to the built-in engine, for training learned components against the
+/// real rules: `reset` starts a game and `step` plays one turn with the orders of the agents.
+///
+/// Assisted bots run in the calling thread, whose log is disabled: an experiment plays far too
+/// many games to keep the logs, and the bots would share one file.
+pub struct Env {
+    seats: Vec<Seat>,
+    engine: Option<Engine>,
+    players: Vec<Player>,
+}
+
+impl Env {
+    /// Player 0 takes the first seat and the other players take turns filling the remaining
+    /// ones, so `[Agent, Bot(..)]` plays one agent against one or three copies of a bot.
+    pub fn new(seats: Vec<Seat>) -> Self {
+        assert!(!seats.is_empty(), "an environment needs seats");
+        Env {
+            seats,
+            engine: None,
+            players: vec![],
+        }
+    }
+
+    fn seat(&self, player: usize) -> &Seat {
+        if player == 0 || self.seats.len() == 1 {
+            &self.seats[0]
+        } else {
+            &self.seats[1 + (player - 1) % (self.seats.len() - 1)]
+        }
+    }
+
+    /// Start a new game and return the first observation of every player.
+    pub fn reset(
+        &mut self,
+        seed: u64,
+        size: usize,
+        players: usize,
+    ) -> Result<Vec<Observation>, EnvError> {
+        let settings = GameSettings {
+            size,
+            players,
+            seed,
+        };
+        settings.validate().map_err(EnvError::Settings)?;
+        Log::disable();
+
+        self.engine = None;
+        self.players.clear();
+        let engine = Engine::new(settings);
+        for id in 0..players {
+            let header = engine.header(id);
+            let player = match *self.seat(id) {
+                Seat::Agent => Player::Agent,
+                Seat::Assisted(ref opts) => Player::Assisted(Box::new(
+                    AssistedBot::start(&header, opts).map_err(EnvError::Protocol)?,
+                )),
+                Seat::Bot(ref spec) => {
+                    let mut bot = spec
+                        .start()
+                        .map_err(|e| EnvError::Bot(spec.label.clone(), e))?;
+                    bot.start(&header)
+                        .map_err(|e| EnvError::Bot(spec.label.clone(), e))?;
+                    Player::Bot(bot)
+                }
+            };
+            self.players.push(player);
+        }
+
+        let observations = (0..players).map(|id| engine.observe(id)).collect();
+        self.engine = Some(engine);
+        Ok(observations)
+    }
+
+    /// Play one turn. `commands[i]` are the orders of player `i` if it is an agent, or the
+    /// steered ships' moves if it is assisted; they are ignored for bots.
+    pub fn step(&mut self, commands: &[Vec<Command>]) -> Result<Step, EnvError> {
+        let engine = match self.engine {
+            Some(ref mut engine) if !engine.is_over() => engine,
+            _ => return Err(EnvError::NoGame),
+        };
+
+        let frame = engine.frame();
+        let no_commands = vec![];
+        let mut lines = vec![];
+        for (id, player) in self.players.iter_mut().enumerate() {
+            let commands = commands.get(id).unwrap_or(&no_commands);
+            let line = match *player {
+                Player::Agent => {
+                    let orders: Vec<&str> = commands.iter().map(|c| c.0.as_ref()).collect();
+                    Ok(orders.join(" "))
+                }
+                Player::Assisted(ref mut bot) => bot
+                    .turn(&frame, commands.iter().filter_map(parse_move).collect())
+                    .map_err(|_| ()),
+                Player::Bot(ref mut bot) => bot.turn(&frame).map_err(|_| ()),
+                Player::Crashed => Ok(String::new()),
+            };
+            lines.push(line.unwrap_or_else(|()| {
+                *player = Player::Crashed;
+                String::new()
+            }));
+        }
+
+        let before: Vec<usize> = (0..lines.len()).map(|id| engine.halite(id)).collect();
+        engine.step(&lines);
+        Ok(Step {
+            observations: (0..lines.len()).map(|id| engine.observe(id)).collect(),
+            rewards: before
+                .iter()
+                .enumerate()
+                .map(|(id, &h)| engine.halite(id) as f64 - h as f64)
+                .collect(),
+            done: engine.is_over(),
+        })
+    }
+}
+
+/// A message to `serve`, e.g. `{"reset": {"size": 32, "players": 2}}` or
+/// `{"step": [["m 3 n", "g"], []]}`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum Request {
+    Reset {
+        seed: Option<u64>,
+        size: usize,
+        players: usize,
+    },
+    Step(Vec<Vec<String>>),
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Response {
+    Reset { observations: Vec<Observation> },
+    Step(Step),
+    Error { error: String },
+}
+
+/// Drive `env` with one JSON request per line of `input` and answer each with one line of
+/// JSON on `output`, so experiments in other languages can use the environment. Errors are
+/// answered with `{"error": ...}` and do not end the session.
+pub fn serve<R: BufRead, W: Write>(mut env: Env, input: R, mut output: W) -> io::Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str(&line) {
+            Ok(Request::Reset {
+                seed,
+                size,
+                players,
+            }) => {
+                let seed = seed.unwrap_or_else(|| Rng::from_time().next_u64() % 1_000_000_000);
+                env.reset(seed, size, players)
+                    .map(|observations| Response::Reset { observations })
+            }
+            Ok(Request::Step(commands)) => {
+                let commands: Vec<Vec<Command>> = commands
+                    .into_iter()
+                    .map(|orders| orders.into_iter().map(Command).collect())
+                    .collect();
+                env.step(&commands).map(Response::Step)
+            }
+            Err(e) => Err(EnvError::Request(e)),
+        };
+        let response = response.unwrap_or_else(|e| Response::Error {
+            error: e.to_string(),
+        });
+        serde_json::to_writer(&mut output, &response)?;
+        writeln!(output)?;
+        output.flush()?;
+    }
+    Ok(())
+}
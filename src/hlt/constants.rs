@@ -1,74 +1,139 @@
-use hlt::log::Log;
-use std::collections::HashMap;
-use std::str::FromStr;
+use hlt::error::{ProtocolError, Result};
+use serde_json::{self, Map, Value};
 
+/// Keys the engine must always send; everything else falls back to the engine's defaults.
+const REQUIRED_KEYS: [&str; 6] = [
+    "NEW_ENTITY_ENERGY_COST",
+    "DROPOFF_COST",
+    "MAX_ENERGY",
+    "MAX_TURNS",
+    "EXTRACT_RATIO",
+    "MOVE_COST_RATIO",
+];
+
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Constants {
+    #[serde(rename = "MAX_ENERGY")]
     pub max_halite: usize,
+    #[serde(rename = "NEW_ENTITY_ENERGY_COST")]
     pub ship_cost: usize,
+    #[serde(rename = "DROPOFF_COST")]
     pub dropoff_cost: usize,
+    #[serde(rename = "MAX_TURNS")]
     pub max_turns: usize,
+    #[serde(rename = "EXTRACT_RATIO")]
     pub extract_ratio: usize,
+    #[serde(rename = "MOVE_COST_RATIO")]
     pub move_cost_ratio: usize,
+    #[serde(rename = "INSPIRATION_ENABLED", default = "default_true")]
     pub inspiration_enabled: bool,
+    #[serde(rename = "INSPIRATION_RADIUS", default = "default_inspiration_radius")]
     pub inspiration_radius: usize,
+    #[serde(
+        rename = "INSPIRATION_SHIP_COUNT",
+        default = "default_inspiration_ship_count"
+    )]
     pub inspiration_ship_count: usize,
+    #[serde(rename = "INSPIRED_EXTRACT_RATIO", default = "default_extract_ratio")]
     pub inspired_extract_ratio: usize,
+    #[serde(
+        rename = "INSPIRED_BONUS_MULTIPLIER",
+        default = "default_bonus_multiplier"
+    )]
     pub inspired_bonus_multiplier: f64,
+    #[serde(
+        rename = "INSPIRED_MOVE_COST_RATIO",
+        default = "default_move_cost_ratio"
+    )]
     pub inspired_move_cost_ratio: usize,
+    #[serde(rename = "CAPTURE_ENABLED", default)]
+    pub capture_enabled: bool,
+    #[serde(rename = "CAPTURE_RADIUS", default = "default_capture_radius")]
+    pub capture_radius: usize,
+    #[serde(
+        rename = "SHIPS_ABOVE_FOR_CAPTURE",
+        default = "default_ships_above_for_capture"
+    )]
+    pub ships_above_for_capture: usize,
+    #[serde(rename = "INITIAL_ENERGY", default = "default_initial_energy")]
+    pub initial_halite: usize,
+    #[serde(rename = "game_seed", default)]
+    pub game_seed: Option<u64>,
+
+    /// Every constant exactly as the engine sent it, including the ones we do not model.
+    #[serde(skip_deserializing)]
+    pub raw: Map<String, Value>,
 }
 
 impl Constants {
-    pub fn new(string_from_engine: &str) -> Constants {
-        let token_iter = string_from_engine.split(|c| " {},:\"\r\n".contains(c));
-        let token_iter = token_iter.filter(|x| !x.is_empty());
-        let tokens: Vec<&str> = token_iter.collect();
-
-        if (tokens.len() % 2) != 0 {
-            Log::panic(
-                "Error: constants: expected even total number of key and value tokens from server.",
-            );
-        }
-
-        let mut map = HashMap::new();
+    pub fn new(string_from_engine: &str) -> Result<Constants> {
+        let raw: Map<String, Value> = serde_json::from_str(string_from_engine).map_err(|_| {
+            ProtocolError::MalformedToken {
+                token: string_from_engine.trim().to_string(),
+                expected: "JSON object of constants",
+            }
+        })?;
 
-        for i in (0..tokens.len()).step_by(2) {
-            map.insert(tokens[i].to_string(), tokens[i + 1].to_string());
+        for key in REQUIRED_KEYS.iter() {
+            if !raw.contains_key(*key) {
+                return Err(ProtocolError::MissingConstant(key.to_string()));
+            }
         }
 
-        Constants {
-            ship_cost: Constants::get_value(&map, "NEW_ENTITY_ENERGY_COST"),
-            dropoff_cost: Constants::get_value(&map, "DROPOFF_COST"),
-            max_halite: Constants::get_value(&map, "MAX_ENERGY"),
-            max_turns: Constants::get_value(&map, "MAX_TURNS"),
-            extract_ratio: Constants::get_value(&map, "EXTRACT_RATIO"),
-            move_cost_ratio: Constants::get_value(&map, "MOVE_COST_RATIO"),
-            inspiration_enabled: Constants::get_value(&map, "INSPIRATION_ENABLED"),
-            inspiration_radius: Constants::get_value(&map, "INSPIRATION_RADIUS"),
-            inspiration_ship_count: Constants::get_value(&map, "INSPIRATION_SHIP_COUNT"),
-            inspired_extract_ratio: Constants::get_value(&map, "INSPIRED_EXTRACT_RATIO"),
-            inspired_bonus_multiplier: Constants::get_value(&map, "INSPIRED_BONUS_MULTIPLIER"),
-            inspired_move_cost_ratio: Constants::get_value(&map, "INSPIRED_MOVE_COST_RATIO"),
-        }
+        let mut constants: Constants =
+            serde_json::from_value(Value::Object(raw.clone())).map_err(|e| {
+                ProtocolError::MalformedToken {
+                    token: e.to_string(),
+                    expected: "constants with valid value types",
+                }
+            })?;
+        constants.raw = raw;
+
+        Ok(constants)
     }
 
-    fn get_value<T: FromStr>(map: &HashMap<String, String>, key: &str) -> T {
-        let s = Constants::get_string(map, key);
-        match s.parse::<T>() {
-            Ok(x) => x,
-            Err(_) => Log::panic(&format!(
-                "Error: constants: for {} got '{}' from server and failed to parse that.",
-                key, s
-            )),
-        }
+    /// Look up any constant sent by the engine, whether or not it has a typed field.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.raw.get(key)
     }
 
-    fn get_string<'a>(map: &'a HashMap<String, String>, key: &str) -> &'a String {
-        match map.get(key) {
-            Some(x) => x,
-            None => Log::panic(&format!(
-                "Error: constants: server did not send {} constant.",
-                key
-            )),
-        }
+    pub fn get_f64(&self, key: &str) -> Option<f64> {
+        self.get(key).and_then(Value::as_f64)
     }
 }
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_inspiration_radius() -> usize {
+    4
+}
+
+fn default_inspiration_ship_count() -> usize {
+    2
+}
+
+fn default_extract_ratio() -> usize {
+    4
+}
+
+fn default_bonus_multiplier() -> f64 {
+    2.0
+}
+
+fn default_move_cost_ratio() -> usize {
+    10
+}
+
+fn default_capture_radius() -> usize {
+    3
+}
+
+fn default_ships_above_for_capture() -> usize {
+    3
+}
+
+fn default_initial_energy() -> usize {
+    5000
+}
@@ -1,4 +1,5 @@
 use hlt::entity::Entity;
+use hlt::error::Result;
 use hlt::input::Input;
 use hlt::position::Position;
 use hlt::DropoffId;
@@ -22,16 +23,16 @@ impl Entity for Dropoff {
 }
 
 impl Dropoff {
-    pub fn generate(input: &mut Input, player_id: PlayerId) -> Dropoff {
-        input.read_and_parse_line();
-        let id = DropoffId(input.next_usize());
-        let x = input.next_i32();
-        let y = input.next_i32();
+    pub fn generate(input: &mut Input, player_id: PlayerId) -> Result<Dropoff> {
+        input.read_and_parse_line()?;
+        let id = DropoffId(input.next_usize()?);
+        let x = input.next_i32()?;
+        let y = input.next_i32()?;
 
-        Dropoff {
+        Ok(Dropoff {
             owner: player_id,
             id,
             position: Position { x, y },
-        }
+        })
     }
 }
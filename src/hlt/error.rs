@@ -0,0 +1,69 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+pub type Result<T> = std::result::Result<T, ProtocolError>;
+
+#[derive(Debug)]
+pub enum ProtocolError {
+    /// The engine closed the connection.
+    UnexpectedEof,
+    /// Reading from or writing to the engine failed.
+    Io(io::Error),
+    /// A line ended before all expected tokens were read.
+    MissingToken { expected: &'static str },
+    /// A token could not be parsed into the expected type.
+    MalformedToken {
+        token: String,
+        expected: &'static str,
+    },
+    /// A required game constant was not sent by the engine.
+    MissingConstant(String),
+    /// Counts or ids in the engine's message do not agree with each other.
+    InconsistentCount {
+        what: &'static str,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl ProtocolError {
+    pub fn is_eof(&self) -> bool {
+        matches!(*self, ProtocolError::UnexpectedEof)
+    }
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProtocolError::UnexpectedEof => write!(f, "unexpected end of input from engine"),
+            ProtocolError::Io(e) => write!(f, "error talking to engine: {}", e),
+            ProtocolError::MissingToken { expected } => {
+                write!(f, "line ended while expecting {}", expected)
+            }
+            ProtocolError::MalformedToken { token, expected } => {
+                write!(f, "can't parse '{}' as {}", token, expected)
+            }
+            ProtocolError::MissingConstant(key) => {
+                write!(f, "engine did not send the {} constant", key)
+            }
+            ProtocolError::InconsistentCount {
+                what,
+                expected,
+                found,
+            } => write!(
+                f,
+                "inconsistent {}: expected {}, found {}",
+                what, expected, found
+            ),
+        }
+    }
+}
+
+impl Error for ProtocolError {}
+
+impl From<io::Error> for ProtocolError {
+    fn from(e: io::Error) -> Self {
+        ProtocolError::Io(e)
+    }
+}
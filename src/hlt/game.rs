@@ -1,6 +1,7 @@
 use hlt::command::Command;
 use hlt::constants::Constants;
 use hlt::dropoff::Dropoff;
+use hlt::error::{ProtocolError, Result};
 use hlt::game_map::GameMap;
 use hlt::input::Input;
 use hlt::log::Log;
@@ -11,10 +12,10 @@ use hlt::DropoffId;
 use hlt::PlayerId;
 use hlt::ShipId;
 use std::collections::HashMap;
+use std::io::Write;
 
 #[derive(Serialize)]
 pub struct Game {
-    #[serde(skip)]
     pub constants: Constants,
     pub turn_number: usize,
     pub my_id: PlayerId,
@@ -25,27 +26,37 @@ pub struct Game {
 
     #[serde(skip)]
     input: Input,
+    #[serde(skip)]
+    output: Box<dyn Write + Send>,
 }
 
 impl Game {
-    pub fn new() -> Game {
-        let mut input = Input::new();
-        let constants = Constants::new(&input.read_and_return_line());
-
-        input.read_and_parse_line();
-        let num_players = input.next_usize();
-        let my_id = PlayerId(input.next_usize());
+    /// Read the game header from `input`. Commands go to `output`.
+    pub fn new(mut input: Input, output: Box<dyn Write + Send>) -> Result<Game> {
+        let constants = Constants::new(&input.read_and_return_line()?)?;
+
+        input.read_and_parse_line()?;
+        let num_players = input.next_usize()?;
+        let my_id = PlayerId(input.next_usize()?);
+
+        if my_id.0 >= num_players {
+            return Err(ProtocolError::InconsistentCount {
+                what: "own player id (number of players)",
+                expected: num_players,
+                found: my_id.0,
+            });
+        }
 
         Log::open(my_id.0);
 
         let mut players: Vec<Player> = Vec::new();
         for _ in 0..num_players {
-            players.push(Player::generate(&mut input));
+            players.push(Player::generate(&mut input)?);
         }
 
-        let map = GameMap::generate(&mut input);
+        let map = GameMap::generate(&mut input)?;
 
-        Game {
+        Ok(Game {
             constants,
             turn_number: 0,
             my_id,
@@ -54,18 +65,21 @@ impl Game {
             dropoffs: HashMap::new(),
             map,
             input,
-        }
+            output,
+        })
     }
 
-    pub fn ready(name: &str) {
-        println!("{}", name);
+    pub fn ready(&mut self, name: &str) -> Result<()> {
+        writeln!(self.output, "{}", name)?;
+        self.output.flush()?;
+        Ok(())
     }
 
-    pub fn update_frame(&mut self) {
+    pub fn update_frame(&mut self) -> Result<()> {
         let input = &mut self.input;
 
-        input.read_and_parse_line();
-        self.turn_number = input.next_usize();
+        input.read_and_parse_line()?;
+        self.turn_number = input.next_usize()?;
 
         Log::log(&format!(
             "=============== TURN {} ================",
@@ -76,11 +90,19 @@ impl Game {
         self.dropoffs.clear();
 
         for _ in 0..self.players.len() {
-            input.read_and_parse_line();
-            let current_player_id = input.next_usize();
-            let num_ships = input.next_usize();
-            let num_dropoffs = input.next_usize();
-            let halite = input.next_usize();
+            input.read_and_parse_line()?;
+            let current_player_id = input.next_usize()?;
+            let num_ships = input.next_usize()?;
+            let num_dropoffs = input.next_usize()?;
+            let halite = input.next_usize()?;
+
+            if current_player_id >= self.players.len() {
+                return Err(ProtocolError::InconsistentCount {
+                    what: "player id in frame (number of players)",
+                    expected: self.players.len(),
+                    found: current_player_id,
+                });
+            }
 
             self.players[current_player_id].update(
                 input,
@@ -90,10 +112,10 @@ impl Game {
                 num_ships,
                 num_dropoffs,
                 halite,
-            );
+            )?;
         }
 
-        self.map.update(input);
+        self.map.update(input)?;
 
         for player in &self.players {
             let shipyard = &player.shipyard;
@@ -104,12 +126,16 @@ impl Game {
                 self.map.at_entity_mut(dropoff).structure = Structure::Dropoff(*dropoff_id);
             }
         }
+
+        Ok(())
     }
 
-    pub fn end_turn(commands: &[Command]) {
+    pub fn end_turn(&mut self, commands: &[Command]) -> Result<()> {
         for command in commands {
-            print!("{} ", command.0);
+            write!(self.output, "{} ", command.0)?;
         }
-        println!();
+        writeln!(self.output)?;
+        self.output.flush()?;
+        Ok(())
     }
 }
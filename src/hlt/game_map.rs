@@ -1,4 +1,5 @@
 use hlt::entity::Entity;
+use hlt::error::{ProtocolError, Result};
 use hlt::input::Input;
 use hlt::map_cell::MapCell;
 use hlt::map_cell::Structure;
@@ -52,32 +53,57 @@ impl GameMap {
         Position { x, y }
     }
 
-    pub fn update(&mut self, input: &mut Input) {
-        input.read_and_parse_line();
-        let update_count = input.next_usize();
+    pub fn update(&mut self, input: &mut Input) -> Result<()> {
+        input.read_and_parse_line()?;
+        let update_count = input.next_usize()?;
 
         for _ in 0..update_count {
-            input.read_and_parse_line();
-            let x = input.next_usize();
-            let y = input.next_usize();
-            let halite = input.next_usize();
+            input.read_and_parse_line()?;
+            let x = input.next_usize()?;
+            let y = input.next_usize()?;
+            let halite = input.next_usize()?;
+
+            if x >= self.width {
+                return Err(ProtocolError::InconsistentCount {
+                    what: "map update x coordinate (map width)",
+                    expected: self.width,
+                    found: x,
+                });
+            }
+            if y >= self.height {
+                return Err(ProtocolError::InconsistentCount {
+                    what: "map update y coordinate (map height)",
+                    expected: self.height,
+                    found: y,
+                });
+            }
 
             self.cells[y][x].halite = halite;
         }
+
+        Ok(())
     }
 
-    pub fn generate(input: &mut Input) -> GameMap {
-        input.read_and_parse_line();
-        let width = input.next_usize();
-        let height = input.next_usize();
+    pub fn generate(input: &mut Input) -> Result<GameMap> {
+        input.read_and_parse_line()?;
+        let width = input.next_usize()?;
+        let height = input.next_usize()?;
 
         let mut cells: Vec<Vec<MapCell>> = Vec::with_capacity(height);
         for y in 0..height {
-            input.read_and_parse_line();
+            input.read_and_parse_line()?;
+
+            if input.remaining_tokens() != width {
+                return Err(ProtocolError::InconsistentCount {
+                    what: "cells in map row",
+                    expected: width,
+                    found: input.remaining_tokens(),
+                });
+            }
 
             let mut row: Vec<MapCell> = Vec::with_capacity(width);
             for x in 0..width {
-                let halite = input.next_usize();
+                let halite = input.next_usize()?;
 
                 let position = Position {
                     x: x as i32,
@@ -94,11 +120,11 @@ impl GameMap {
             cells.push(row);
         }
 
-        GameMap {
+        Ok(GameMap {
             width,
             height,
             cells,
-        }
+        })
     }
 
     pub fn iter(&self) -> impl Iterator<Item = &MapCell> {
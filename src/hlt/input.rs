@@ -1,59 +1,81 @@
-use hlt::log::Log;
-use std::io::stdin;
-use std::process::exit;
+use hlt::error::{ProtocolError, Result};
+use std::io::{stdin, BufRead, BufReader, Write};
 use std::str::FromStr;
 
+/// Reads the engine's messages, by default from stdin.
 pub struct Input {
+    source: Box<dyn BufRead + Send>,
+    transcript: Option<Box<dyn Write + Send>>,
     tokens: Vec<String>,
     current_token: usize,
 }
 
 impl Input {
-    pub fn new() -> Input {
+    pub fn new(source: Box<dyn BufRead + Send>) -> Input {
         Input {
+            source,
+            transcript: None,
             tokens: Vec::new(),
             current_token: 0,
         }
     }
 
-    pub fn read_and_return_line(&mut self) -> String {
+    pub fn stdin() -> Input {
+        Input::new(Box::new(BufReader::new(stdin())))
+    }
+
+    /// Copy every line read to `transcript`, so the game can be played back later.
+    pub fn record(&mut self, transcript: Box<dyn Write + Send>) {
+        self.transcript = Some(transcript);
+    }
+
+    pub fn read_and_return_line(&mut self) -> Result<String> {
         let mut buf = String::new();
-        match stdin().read_line(&mut buf) {
-            Ok(_) => (),
-            Err(_) => {
-                Log::log("Input connection from server closed. Exiting...");
-                Log::flush();
-                exit(0);
-            }
+        if self.source.read_line(&mut buf)? == 0 {
+            return Err(ProtocolError::UnexpectedEof);
+        }
+        if let Some(ref mut transcript) = self.transcript {
+            transcript.write_all(buf.as_bytes())?;
+            transcript.flush()?;
         }
-        buf
+        Ok(buf)
     }
 
-    pub fn read_and_parse_line(&mut self) {
-        let buf = self.read_and_return_line();
+    pub fn read_and_parse_line(&mut self) -> Result<()> {
+        let buf = self.read_and_return_line()?;
         let token_iter = buf.split_whitespace();
         let token_iter = token_iter.filter(|x| !x.is_empty());
         let token_iter = token_iter.map(|x| x.to_string());
         self.tokens = token_iter.collect();
         self.current_token = 0;
+        Ok(())
     }
 
-    pub fn next_i32(&mut self) -> i32 {
+    /// Number of tokens on the current line that have not been read yet.
+    pub fn remaining_tokens(&self) -> usize {
+        self.tokens.len() - self.current_token
+    }
+
+    pub fn next_i32(&mut self) -> Result<i32> {
         self.next()
     }
 
-    pub fn next_usize(&mut self) -> usize {
+    pub fn next_usize(&mut self) -> Result<usize> {
         self.next()
     }
 
-    pub fn next<T: FromStr>(&mut self) -> T {
-        let token = &self.tokens[self.current_token];
-        self.current_token += 1;
+    pub fn next<T: FromStr>(&mut self) -> Result<T> {
+        let expected = std::any::type_name::<T>();
 
-        let result = match token.parse() {
-            Ok(x) => x,
-            Err(_) => Log::panic(&format!("Can't parse '{}'.", token)),
+        let token = match self.tokens.get(self.current_token) {
+            Some(token) => token,
+            None => return Err(ProtocolError::MissingToken { expected }),
         };
-        result
+        self.current_token += 1;
+
+        token.parse().map_err(|_| ProtocolError::MalformedToken {
+            token: token.clone(),
+            expected,
+        })
     }
 }
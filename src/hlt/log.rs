@@ -1,17 +1,20 @@
+use std::cell::RefCell;
 use std::fs::File;
 use std::io::Write;
 use std::process::exit;
-use std::sync::Mutex;
 use std::time::SystemTime;
 use std::time::UNIX_EPOCH;
 
-lazy_static! {
-    static ref LOG: Mutex<Log> = Mutex::new(Log::new());
+// Every thread has its own log, so bots running side by side in one process (see `engine`)
+// do not write into each other's files.
+thread_local! {
+    static LOG: RefCell<Log> = RefCell::new(Log::new());
 }
 
 pub struct Log {
     log_buffer: Option<Vec<String>>,
     file: Option<File>,
+    enabled: bool,
 }
 
 impl Log {
@@ -19,15 +22,35 @@ impl Log {
         Log {
             log_buffer: Some(Vec::new()),
             file: None,
+            enabled: true,
         }
     }
 
+    /// Drop all further messages of this thread and never create a log file.
+    pub fn disable() {
+        LOG.with(|log| {
+            let mut log = log.borrow_mut();
+            log.enabled = false;
+            log.log_buffer = None;
+        });
+    }
+
+    pub fn is_enabled() -> bool {
+        LOG.with(|log| log.borrow().enabled)
+    }
+
     pub fn open(bot_id: usize) {
-        let mut log = LOG.lock().unwrap();
+        LOG.with(|log| Log::open_inner(&mut log.borrow_mut(), bot_id))
+    }
+
+    fn open_inner(log: &mut Log, bot_id: usize) {
+        if !log.enabled {
+            return;
+        }
 
         if log.file.is_some() {
             Log::panic_inner(
-                &mut log,
+                log,
                 &format!(
                     "Error: log: tried to open({}) but we have already opened before.",
                     bot_id
@@ -46,7 +69,13 @@ impl Log {
     }
 
     pub fn log(message: &str) {
-        let mut log = LOG.lock().unwrap();
+        LOG.with(|log| Log::log_inner(&mut log.borrow_mut(), message))
+    }
+
+    fn log_inner(log: &mut Log, message: &str) {
+        if !log.enabled {
+            return;
+        }
 
         match &mut log.file {
             Some(file) => {
@@ -65,22 +94,23 @@ impl Log {
     }
 
     pub fn flush() {
-        let mut log = LOG.lock().unwrap();
-
-        match &mut log.file {
-            Some(file) => {
+        LOG.with(|log| {
+            if let Some(file) = &mut log.borrow_mut().file {
                 file.flush().unwrap();
             }
-            None => (),
-        }
+        })
     }
 
     pub fn panic(message: &str) -> ! {
-        let mut log = LOG.lock().unwrap();
-        Log::panic_inner(&mut log, message)
+        LOG.with(|log| Log::panic_inner(&mut log.borrow_mut(), message))
     }
 
     fn panic_inner(log: &mut Log, message: &str) -> ! {
+        if !log.enabled {
+            // a quiet bot may share the process with others, so only take down this thread
+            panic!("{}", message);
+        }
+
         if log.file.is_none() {
             let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
             let filename = format!("bot-unknown-{}.log", timestamp.as_secs());
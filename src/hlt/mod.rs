@@ -9,6 +9,8 @@ pub mod dropoff;
 #[allow(dead_code)]
 pub mod entity;
 #[allow(dead_code)]
+pub mod error;
+#[allow(dead_code)]
 pub mod game;
 #[allow(dead_code)]
 pub mod game_map;
@@ -28,7 +30,7 @@ pub mod ship;
 pub mod shipyard;
 
 #[allow(dead_code)]
-mod input;
+pub mod input;
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize)]
 pub struct PlayerId(pub usize);
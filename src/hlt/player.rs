@@ -1,4 +1,5 @@
 use hlt::dropoff::Dropoff;
+use hlt::error::Result;
 use hlt::input::Input;
 use hlt::position::Position;
 use hlt::ship::Ship;
@@ -27,29 +28,31 @@ impl Player {
         num_ships: usize,
         num_dropoffs: usize,
         halite: usize,
-    ) {
+    ) -> Result<()> {
         self.halite = halite;
 
         self.ship_ids.clear();
         for _ in 0..num_ships {
-            let ship = Ship::generate(input, self.id, max_halite);
+            let ship = Ship::generate(input, self.id, max_halite)?;
             self.ship_ids.push(ship.id);
             ships.insert(ship.id, ship);
         }
 
         self.dropoff_ids.clear();
         for _ in 0..num_dropoffs {
-            let dropoff = Dropoff::generate(input, self.id);
+            let dropoff = Dropoff::generate(input, self.id)?;
             self.dropoff_ids.push(dropoff.id);
             dropoffs.insert(dropoff.id, dropoff);
         }
+
+        Ok(())
     }
 
-    pub fn generate(input: &mut Input) -> Player {
-        input.read_and_parse_line();
-        let id = PlayerId(input.next_usize());
-        let shipyard_x = input.next_i32();
-        let shipyard_y = input.next_i32();
+    pub fn generate(input: &mut Input) -> Result<Player> {
+        input.read_and_parse_line()?;
+        let id = PlayerId(input.next_usize()?);
+        let shipyard_x = input.next_i32()?;
+        let shipyard_y = input.next_i32()?;
 
         let shipyard = Shipyard {
             owner: id,
@@ -59,12 +62,12 @@ impl Player {
             },
         };
 
-        Player {
+        Ok(Player {
             id,
             shipyard,
             halite: 0,
             ship_ids: Vec::new(),
             dropoff_ids: Vec::new(),
-        }
+        })
     }
 }
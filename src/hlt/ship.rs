@@ -1,5 +1,6 @@
 use hlt::command::Command;
 use hlt::entity::Entity;
+use hlt::error::Result;
 use hlt::input::Input;
 use hlt::position::Position;
 use hlt::PlayerId;
@@ -26,20 +27,20 @@ impl Ship {
         Command::transform_ship_into_dropoff_site(self.id)
     }
 
-    pub fn generate(input: &mut Input, player_id: PlayerId, max_halite: usize) -> Ship {
-        input.read_and_parse_line();
-        let id = ShipId(input.next_usize());
-        let x = input.next_i32();
-        let y = input.next_i32();
-        let halite = input.next_usize();
+    pub fn generate(input: &mut Input, player_id: PlayerId, max_halite: usize) -> Result<Ship> {
+        input.read_and_parse_line()?;
+        let id = ShipId(input.next_usize()?);
+        let x = input.next_i32()?;
+        let y = input.next_i32()?;
+        let halite = input.next_usize()?;
 
-        Ship {
+        Ok(Ship {
             owner: player_id,
             id,
             position: Position { x, y },
             halite,
             max_halite,
-        }
+        })
     }
 }
 
@@ -1,5 +1,3 @@
-#[macro_use]
-extern crate lazy_static;
 extern crate pathfinding;
 //extern crate rand;
 extern crate serde;
@@ -7,9 +5,13 @@ extern crate serde_json;
 #[macro_use]
 extern crate serde_derive;
 
+use events::{EventExtractor, GameEvent};
+use expectations::Expectations;
 use hlt::command::Command;
 use hlt::direction::Direction;
+use hlt::error::ProtocolError;
 use hlt::game::Game;
+use hlt::input::Input;
 use hlt::log::Log;
 use hlt::map_cell::Structure;
 use hlt::navi::Navi;
@@ -17,23 +19,43 @@ use hlt::player::Player;
 use hlt::position::Position;
 use hlt::ship::Ship;
 use hlt::ShipId;
+use treasury::{Expense, Treasury};
 //use rand::SeedableRng;
 //use rand::XorShiftRng;
 use std::collections::{BinaryHeap, HashMap};
-use std::env;
 use std::io::prelude::*;
 //use std::time::SystemTime;
 //use std::time::UNIX_EPOCH;
 use utils::NumericCast;
 
 mod ai_manager;
+mod baselines;
+mod behavior_tree;
+mod bt_tasks;
+mod cli;
 mod commander;
 mod config;
+mod dropoff_planner;
+mod engine;
+mod events;
+mod expectations;
+mod gym;
 mod hlt;
 mod movement_predictor;
 mod navigation_system;
+mod neural;
+mod npy;
+mod optimizer;
 mod pda;
+mod rating;
+mod rng;
 mod ship_ai;
+mod spawn_model;
+mod statistics;
+mod tournament;
+mod training_data;
+mod treasury;
+mod utility;
 mod utils;
 
 #[derive(Debug, Eq, PartialEq)]
@@ -76,14 +98,27 @@ pub struct GameState {
     #[serde(skip)]
     gns: navigation_system::NavigationSystem,
 
+    #[serde(skip)]
+    dropoff_planner: dropoff_planner::DropoffPlanner,
+
+    #[serde(skip)]
+    policy: Option<neural::Policy>,
+
+    #[serde(skip)]
+    training_data: Option<training_data::TrainingData>,
+
     #[serde(skip)]
     command_queue: Vec<Command>,
 
     ship_map: Vec<Vec<Option<ShipId>>>,
 
-    collect_statistic: Vec<f64>,
-    last_halite: usize,
-    total_spent: usize,
+    #[serde(skip)]
+    event_extractor: EventExtractor,
+    events: Vec<GameEvent>,
+    expectations: Expectations,
+
+    statistics: statistics::Statistics,
+    treasury: Treasury,
 
     pheromones: Vec<Vec<f64>>,
     pheromones_backbuffer: Vec<Vec<f64>>,
@@ -92,24 +127,47 @@ pub struct GameState {
     halite_density: Vec<Vec<i32>>,
     return_map_directions: Vec<Vec<Direction>>,
     return_cumultive_costs: Vec<Vec<i32>>,
-
-    halite_percentiles: Vec<usize>,
-    avg_return_length: f64,
 }
 
 impl GameState {
-    fn new(cfg_file: &str) -> Self {
-        let game = Game::new();
-        let state = GameState {
-            config: config::Config::from_file(cfg_file),
+    fn new(
+        input: Input,
+        output: Box<dyn Write + Send>,
+        opts: &BotOptions,
+    ) -> Result<Self, ProtocolError> {
+        let game = Game::new(input, output)?;
+        let mode = config::GameMode {
+            players: game.players.len(),
+            width: game.map.width,
+            height: game.map.height,
+        };
+        let cfg_file = opts.config_file.as_ref().map(String::as_ref);
+        let config = config::Config::load(cfg_file, &mode, &opts.overrides)
+            .unwrap_or_else(|e| Log::panic(&format!("Error: failed to load config: {}", e)));
+        Log::log(&format!(
+            "Config: {}",
+            serde_json::to_string(&config).expect("config serializes")
+        ));
+        let policy = neural::Policy::load(&config.ships.policy)
+            .unwrap_or_else(|e| Log::panic(&format!("Error: failed to load policy: {}", e)));
+        let mut state = GameState {
+            config,
             navi: Navi::new(game.map.width, game.map.height),
             mp: movement_predictor::MovementPredictor::new(game.map.width, game.map.height),
             gns: navigation_system::NavigationSystem::new(game.map.width, game.map.height),
+            dropoff_planner: dropoff_planner::DropoffPlanner::new(game.map.width, game.map.height),
+            policy,
+            training_data: opts
+                .export_dir
+                .as_ref()
+                .map(|dir| training_data::TrainingData::new(dir)),
             command_queue: vec![],
             ship_map: vec![vec![None; game.map.width]; game.map.height],
-            collect_statistic: Vec::with_capacity(game.constants.max_turns),
-            last_halite: 5000,
-            total_spent: 0,
+            event_extractor: EventExtractor::new(&game),
+            events: vec![],
+            expectations: Expectations::new(),
+            statistics: statistics::Statistics::new(&game),
+            treasury: Treasury::new(),
 
             pheromones: vec![vec![0.0; game.map.width]; game.map.height],
             pheromones_backbuffer: vec![vec![0.0; game.map.width]; game.map.height],
@@ -119,19 +177,19 @@ impl GameState {
             return_map_directions: vec![vec![Direction::Still; game.map.width]; game.map.height],
             return_cumultive_costs: vec![vec![0; game.map.width]; game.map.height],
 
-            halite_percentiles: vec![0; 101],
-            avg_return_length: 0.0,
-
             game,
         };
 
-        Game::ready("MyRustBot");
+        state.game.ready("MyRustBot")?;
 
-        state
+        Ok(state)
     }
 
-    fn update_frame(&mut self) {
-        self.game.update_frame();
+    fn update_frame(&mut self) -> Result<(), ProtocolError> {
+        self.game.update_frame()?;
+        self.treasury.reconcile(&self.game);
+        self.events = self.event_extractor.update_frame(&self.game);
+        self.expectations.check(&self.game, &self.events);
 
         self.ship_map = vec![vec![None; self.game.map.width]; self.game.map.height];
         for (&id, pos) in self.game.ships.iter().map(|(id, s)| (id, s.position)) {
@@ -144,41 +202,28 @@ impl GameState {
 
         self.compute_halite_density();
         self.compute_return_map();
+        self.dropoff_planner
+            .update_frame(&self.game, &self.config.expansion);
 
         self.update_pheromones();
 
-        let mut map_halite: Vec<_> = self.game.map.iter().map(|cell| cell.halite).collect();
-        map_halite.sort_unstable();
-        let n = map_halite.len() - 1;
-        for i in 0..=100 {
-            self.halite_percentiles[i] = map_halite[(n * i) / 100];
-        }
-        Log::log(&format!("Halite quartiles: {:?}", self.halite_percentiles));
-
-        /*if self.me().halite > self.last_halite {
-            let diff =
-                (self.me().halite - self.last_halite) as f64 / self.me().ship_ids.len() as f64;
-            self.collect_statistic.push(diff);
-        } else {
-            self.collect_statistic.push(0.0);
-        }*/
-
-        let delta = (self.me().halite + self.total_spent - self.last_halite) as f64;
-        let nship = self.me().ship_ids.len() as f64;
-        self.collect_statistic.push(delta / nship);
-
-        self.last_halite = self.me().halite + self.total_spent;
+        self.statistics
+            .update_frame(&self.game, &self.events, self.treasury.total_spent());
 
         self.command_queue.clear();
+
+        Ok(())
     }
 
-    fn finalize_frame(&mut self, _runid: &str, dumpfile: Option<&str>) {
+    fn finalize_frame(&mut self, dumpfile: Option<&str>) -> Result<(), ProtocolError> {
         //Log::log(&format!("issuing commands: {:?}", command_queue));
 
-        /*if self.game.turn_number == self.game.constants.max_turns - 5 {
-            Log::log("dumping neural net");
-            self.collector_net.dump(&format!("netdump{}-{}.txt", runid, self.game.my_id.0));
-        }*/
+        self.expectations.predict(&self.game, &self.command_queue);
+
+        if let Some(mut data) = self.training_data.take() {
+            data.record(self);
+            self.training_data = Some(data);
+        }
 
         if let Some(file) = dumpfile {
             let mut file = std::fs::OpenOptions::new()
@@ -191,21 +236,25 @@ impl GameState {
             file.write_all(b"\n===\n").unwrap();
         }
 
-        Game::end_turn(&self.command_queue);
+        self.game.end_turn(&self.command_queue)?;
+
+        if self.game.turn_number >= self.game.constants.max_turns {
+            if Log::is_enabled() {
+                let filename = format!("bot-{}.stats.json", self.game.my_id.0);
+                if let Err(e) = self.statistics.write_summary(&self.game, &filename) {
+                    Log::log(&format!("failed to write {}: {}", filename, e));
+                }
+            }
 
-        if self.game.turn_number >= self.game.constants.max_turns - 1 {
-            Log::log(&format!("collection rate: {:?}", self.collect_statistic));
+            if let Some(data) = self.training_data.take() {
+                match data.write(self) {
+                    Ok(file) => Log::log(&format!("training data written to {}", file)),
+                    Err(e) => Log::log(&format!("failed to write training data: {}", e)),
+                }
+            }
         }
-    }
 
-    fn notify_return(&mut self, turns_taken: usize) {
-        const UPDATE_RATE: f64 = 0.9;
-        self.avg_return_length =
-            self.avg_return_length * UPDATE_RATE + turns_taken as f64 * (1.0 - UPDATE_RATE);
-        Log::log(&format!(
-            "Average return length: {}",
-            self.avg_return_length
-        ));
+        Ok(())
     }
 
     fn rounds_left(&self) -> usize {
@@ -243,41 +292,17 @@ impl GameState {
         }.map(|ship| ship.id)
     }
 
-    fn distance_to_nearest_dropoff(&self, id: ShipId) -> usize {
-        let pos = self.get_ship(id).position;
-        let dist = self
-            .game
-            .map
-            .calculate_distance(&self.me().shipyard.position, &pos);
-
-        self.me()
-            .dropoff_ids
-            .iter()
-            .map(|did| self.game.dropoffs[did].position)
-            .map(|p| self.game.map.calculate_distance(&p, &pos))
-            .fold(dist, |dist, d| dist.min(d))
-    }
-
-    fn ships_in_range<'a>(&'a self, pos: Position, r: usize) -> impl Iterator<Item = ShipId> + 'a {
-        self.my_ships().filter(move |&id| {
-            self.game
-                .map
-                .calculate_distance(&pos, &self.get_ship(id).position)
-                <= r
-        })
-    }
-
     fn try_build_dropoff(&mut self, id: ShipId) -> bool {
-        if self.me().halite < self.game.constants.dropoff_cost {
+        let pos = self.get_ship(id).position;
+        let cost = Treasury::dropoff_cost(&self.game, self.get_ship(id));
+        if !self.treasury.try_spend(Expense::Dropoff(id, pos), cost) {
             return false;
         }
 
         let cmd = self.get_ship_mut(id).make_dropoff();
         self.command_queue.push(cmd);
 
-        self.total_spent += self.game.constants.dropoff_cost; // assuming the spawn is always successful (it should be...)
-
-        self.avg_return_length = 0.0;
+        self.statistics.reset_return_length();
 
         true
     }
@@ -397,6 +422,12 @@ impl GameState {
     }
 
     fn compute_return_map(&mut self) {
+        let exit = self
+            .config
+            .navigation
+            .return_map_exit
+            .and_then(Direction::from_char_encoding);
+
         for cc in self
             .return_cumultive_costs
             .iter_mut()
@@ -431,7 +462,7 @@ impl GameState {
 
             for d in Direction::get_all_cardinals() {
                 // make sure we leave an exit open
-                if dir == Direction::Still && d == Direction::East {
+                if dir == Direction::Still && Some(d) == exit {
                     continue;
                 }
                 let p = pos.directional_offset(d.invert_direction());
@@ -449,7 +480,7 @@ impl GameState {
     }
 
     fn compute_halite_density(&mut self) {
-        let r = 5_i32;
+        let r = self.config.expansion.halite_density_radius;
         let n = 2 * r * (r + 1) + 1; // number of pixels within manhatten distance of r
 
         for (i, row) in self.halite_density.iter_mut().enumerate() {
@@ -509,7 +540,7 @@ impl GameState {
                 let dphi = if ship.owner == self.game.my_id {
                     (phi0 - cap).min(0.0) * self.config.pheromones.ship_absorbtion
                 } else {
-                    (cargo - phi0).max(0.0) * 0.1
+                    (cargo - phi0).max(0.0) * self.config.pheromones.enemy_cargo_emission
                 };
 
                 self.pheromones_backbuffer[p.y as usize][p.x as usize] +=
@@ -537,6 +568,13 @@ impl GameState {
         let (i, j) = (pos.y as usize, pos.x as usize);
         self.pheromones[i][j]
     }
+
+    /// A ship AI state is about to step, so it is the one to credit with the ship's move.
+    fn note_state(&mut self, id: ShipId, name: &'static str) {
+        if let Some(ref mut data) = self.training_data {
+            data.note_state(id, name);
+        }
+    }
 }
 
 /*struct Commander {
@@ -689,55 +727,62 @@ state.command_queue.extend(state.gns.execute());
 }
 }*/
 
-fn main() {
-    /*let rng_seed: u64 = if args.len() > 1 {
-        args[1].parse().unwrap()
-    } else {
-        SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs()
-    };*/
-    /*let seed_bytes: Vec<u8> = (0..16).map(|x| ((rng_seed >> (x % 8)) & 0xFF) as u8).collect();
-    let mut rng: XorShiftRng = SeedableRng::from_seed([
-        seed_bytes[0], seed_bytes[1], seed_bytes[2], seed_bytes[3],
-        seed_bytes[4], seed_bytes[5], seed_bytes[6], seed_bytes[7],
-        seed_bytes[8], seed_bytes[9], seed_bytes[10], seed_bytes[11],
-        seed_bytes[12], seed_bytes[13], seed_bytes[14], seed_bytes[15]
-    ]);*/
-
-    /*let net = if args.len() > 1 {
-        Log::log(&format!("loading network from file {}", args[1]));
-        movement::CollectorNeuralNet::from_file(&args[1])
-    } else {
-        Log::log("using default network");
-        movement::CollectorNeuralNet::new()
-    };*/
-
-    let mut cfg_file = "config.json".to_string();
-    let mut dump_file = None;
-    let mut runid = String::new();
-
-    let mut args = env::args().skip(1);
-    while let Some(arg) = args.next() {
-        match arg.as_ref() {
-            "-c" | "--config" => cfg_file = args.next().unwrap(),
-            "-d" | "--dump" => dump_file = args.next(),
-            "-r" | "--runid" => runid = args.next().unwrap(),
-            _ => panic!("Invalid argument: {}", arg),
-        }
+/// How to run the bot, besides the game it plays.
+#[derive(Debug, Clone, Default)]
+pub struct BotOptions {
+    /// Config file on top of the embedded one.
+    pub config_file: Option<String>,
+    /// `--set key=value` overrides.
+    pub overrides: Vec<String>,
+    /// Append the serialized game state to this file every turn.
+    pub dump_file: Option<String>,
+    /// No log or statistics files, e.g. for bots in local tournaments.
+    pub quiet: bool,
+    /// Play a reference strategy instead of this bot.
+    pub baseline: Option<baselines::Baseline>,
+    /// Save per-ship training data of every game in this directory.
+    pub export_dir: Option<String>,
+}
+
+/// Play one game, reading the engine's messages from `input` and writing commands to `output`.
+pub fn run_bot(
+    input: Input,
+    output: Box<dyn Write + Send>,
+    opts: &BotOptions,
+) -> Result<(), ProtocolError> {
+    if opts.quiet {
+        Log::disable();
+    }
+
+    if let Some(baseline) = opts.baseline {
+        return baselines::run(baseline, input, output);
     }
 
-    Log::log(&format!("using config file: {}", cfg_file));
+    match opts.config_file {
+        Some(ref file) => Log::log(&format!("using config file: {}", file)),
+        None => Log::log("using embedded config"),
+    }
 
     let mut ai_mgr = ai_manager::AiManager::new();
-    let mut game = GameState::new(&cfg_file);
+    let mut game = GameState::new(input, output, opts)?;
 
     loop {
-        game.update_frame();
+        match game.update_frame() {
+            Ok(()) => {}
+            Err(ref e) if e.is_eof() => {
+                Log::log("Input connection from server closed. Exiting...");
+                Log::flush();
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        }
 
         ai_mgr.think(&mut game);
 
-        game.finalize_frame(&runid, dump_file.as_ref().map(String::as_ref));
+        game.finalize_frame(opts.dump_file.as_ref().map(String::as_ref))?;
     }
 }
+
+fn main() {
+    cli::main()
+}
@@ -99,6 +99,16 @@ impl MovementPredictor {
         self.threat_level[pos.y as usize][pos.x as usize] != Threat::Clear
     }
 
+    /// 0 for a clear position, 1 if an opponent could move there and 2 if one is there.
+    pub fn threat_level(&self, pos: Position) -> usize {
+        let pos = self.normalize(pos);
+        match self.threat_level[pos.y as usize][pos.x as usize] {
+            Threat::Clear => 0,
+            Threat::Reachable => 1,
+            Threat::Occupied => 2,
+        }
+    }
+
     pub fn normalize(&self, position: Position) -> Position {
         let width = self.threat_level[0].len() as i32;
         let height = self.threat_level.len() as i32;
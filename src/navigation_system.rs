@@ -103,6 +103,26 @@ impl NavigationSystem {
         );
     }
 
+    /// The costs a ship gave to `plan_move` this turn, in the order stay, north, south, east,
+    /// west.
+    pub fn planned_costs(&self, id: ShipId) -> Option<[i64; 5]> {
+        self.ships.get(&Actor::Ship(id)).map(|row| {
+            let mut costs = [0; 5];
+            for (c, &(_, cost)) in costs.iter_mut().zip(row) {
+                *c = cost;
+            }
+            costs
+        })
+    }
+
+    /// The move `solve_moves` assigned to a ship.
+    pub fn assigned_move(&self, id: ShipId) -> Option<Direction> {
+        self.final_actions.iter().find_map(|action| match *action {
+            Action::Move(i, d) if i == id => Some(d),
+            _ => None,
+        })
+    }
+
     fn pos(&mut self, p: Position) -> usize {
         let p = self.normalize(p);
         match self.position_indices.get(&p) {
@@ -0,0 +1,234 @@
+use config;
+use hlt::game_map::GameMap;
+use hlt::position::Position;
+use npy::{self, Array, NpyError};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+/// A fully connected layer.
+struct Dense {
+    inputs: usize,
+    outputs: usize,
+    /// One row of `inputs` weights per output.
+    weights: Vec<f64>,
+    bias: Vec<f64>,
+}
+
+impl Dense {
+    fn apply(&self, x: &[f64]) -> Vec<f64> {
+        self.weights
+            .chunks(self.inputs)
+            .zip(&self.bias)
+            .map(|(row, b)| b + row.iter().zip(x).map(|(w, x)| w * x).sum::<f64>())
+            .collect()
+    }
+}
+
+/// A small multilayer perceptron with ReLU hidden layers and a linear output layer, as
+/// trained by the scripts in `machine_learning/`.
+pub struct Mlp {
+    layers: Vec<Dense>,
+}
+
+impl Mlp {
+    /// Build the network from the weights `w1`, `w2`, ... and the optional biases `b1`, `b2`,
+    /// ... of its layers. `wN` has the shape `(outputs, inputs...)`; the input dimensions are
+    /// flattened, so a `w1` of shape `(15, 5, 5)` takes a 5x5 window.
+    ///
+    /// The per-move networks of `policy_gradient.py`, with `w1` of shape
+    /// `(moves, hidden, rows, columns)` and `w2` of shape `(hidden,)`, have hidden units of their
+    /// own for each move, which share the output weights `w2`. They are converted into the
+    /// equivalent plain network.
+    pub fn from_arrays(arrays: &BTreeMap<String, Array>) -> Result<Self, String> {
+        let weights = |i: usize| arrays.get(&format!("w{}", i));
+        if let (Some(w1), Some(w2), None) = (weights(1), weights(2), weights(3)) {
+            if w1.shape.len() == 4 && w2.shape.len() == 1 {
+                return Mlp::per_move(w1, w2);
+            }
+        }
+
+        let mut layers: Vec<Dense> = vec![];
+        while let Some(w) = weights(layers.len() + 1) {
+            let n = layers.len() + 1;
+            if w.shape.len() < 2 {
+                return Err(format!("w{} has shape {:?}, which is no layer", n, w.shape));
+            }
+            let outputs = w.shape[0];
+            let inputs = w.shape[1..].iter().product();
+            if let Some(previous) = layers.last() {
+                if previous.outputs != inputs {
+                    return Err(format!(
+                        "w{} takes {} inputs, but the layer before has {} outputs",
+                        n, inputs, previous.outputs
+                    ));
+                }
+            }
+            let bias = match arrays.get(&format!("b{}", n)) {
+                Some(b) if b.data.len() == outputs => b.data.clone(),
+                Some(b) => {
+                    return Err(format!(
+                        "b{} has shape {:?}, but the layer has {} outputs",
+                        n, b.shape, outputs
+                    ))
+                }
+                None => vec![0.0; outputs],
+            };
+            layers.push(Dense {
+                inputs,
+                outputs,
+                weights: w.data.clone(),
+                bias,
+            });
+        }
+
+        if layers.is_empty() {
+            return Err("no layer weights w1, w2, ...".to_string());
+        }
+        Ok(Mlp { layers })
+    }
+
+    fn per_move(w1: &Array, w2: &Array) -> Result<Self, String> {
+        let (moves, hidden) = (w1.shape[0], w1.shape[1]);
+        if w2.data.len() != hidden {
+            return Err(format!(
+                "w2 has shape {:?}, but w1 has {} hidden units per move",
+                w2.shape, hidden
+            ));
+        }
+
+        // the hidden units of move k are rows k * hidden .. (k + 1) * hidden, which is just
+        // how w1 is laid out
+        let first = Dense {
+            inputs: w1.shape[2] * w1.shape[3],
+            outputs: moves * hidden,
+            weights: w1.data.clone(),
+            bias: vec![0.0; moves * hidden],
+        };
+        let mut weights = vec![0.0; moves * moves * hidden];
+        for k in 0..moves {
+            let row = k * moves * hidden + k * hidden;
+            weights[row..row + hidden].copy_from_slice(&w2.data);
+        }
+        let second = Dense {
+            inputs: moves * hidden,
+            outputs: moves,
+            weights,
+            bias: vec![0.0; moves],
+        };
+        Ok(Mlp {
+            layers: vec![first, second],
+        })
+    }
+
+    pub fn inputs(&self) -> usize {
+        self.layers[0].inputs
+    }
+
+    pub fn outputs(&self) -> usize {
+        self.layers[self.layers.len() - 1].outputs
+    }
+
+    pub fn forward(&self, input: &[f64]) -> Vec<f64> {
+        let mut x = input.to_vec();
+        for (i, layer) in self.layers.iter().enumerate() {
+            x = layer.apply(&x);
+            if i + 1 < self.layers.len() {
+                for v in &mut x {
+                    *v = v.max(0.0);
+                }
+            }
+        }
+        x
+    }
+}
+
+#[derive(Debug)]
+pub enum PolicyError {
+    Load(String, NpyError),
+    /// The weights do not make a usable network.
+    Network(String, String),
+}
+
+impl fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PolicyError::Load(file, e) => write!(f, "can't load {}: {}", file, e),
+            PolicyError::Network(file, e) => write!(f, "{}: {}", file, e),
+        }
+    }
+}
+
+impl Error for PolicyError {}
+
+/// A network that scores the moves of a collecting ship by the halite in a square window
+/// around it.
+///
+/// Like in the training scripts, the window is indexed `[row][column]` with rows from north
+/// to south, and the network returns one logit per move in the order stay, south, east,
+/// north, west.
+pub struct Policy {
+    net: Mlp,
+    radius: i32,
+    input_scale: f64,
+    cost_scale: f64,
+}
+
+impl Policy {
+    /// Load the configured network, if there is one.
+    pub fn load(cfg: &config::Policy) -> Result<Option<Self>, PolicyError> {
+        let file = match cfg.weights {
+            Some(ref file) => file,
+            None => return Ok(None),
+        };
+        let invalid = |e: String| PolicyError::Network(file.clone(), e);
+        let arrays = npy::load(file).map_err(|e| PolicyError::Load(file.clone(), e))?;
+        let net = Mlp::from_arrays(&arrays).map_err(invalid)?;
+
+        let side = (net.inputs() as f64).sqrt().round() as usize;
+        if side * side != net.inputs() || side.is_multiple_of(2) {
+            return Err(invalid(format!(
+                "the network takes {} inputs, which is no window around a ship",
+                net.inputs()
+            )));
+        }
+        if net.outputs() != 5 {
+            return Err(invalid(format!(
+                "the network has {} outputs instead of one per move",
+                net.outputs()
+            )));
+        }
+
+        Ok(Some(Policy {
+            net,
+            radius: (side / 2) as i32,
+            input_scale: cfg.input_scale,
+            cost_scale: cfg.cost_scale,
+        }))
+    }
+
+    fn features(&self, map: &GameMap, pos: Position) -> Vec<f64> {
+        let r = self.radius;
+        let mut x = Vec::with_capacity(self.net.inputs());
+        for dy in -r..=r {
+            for dx in -r..=r {
+                let p = Position {
+                    x: pos.x + dx,
+                    y: pos.y + dy,
+                };
+                x.push(map.at_position(&p).halite as f64 * self.input_scale);
+            }
+        }
+        x
+    }
+
+    /// `plan_move` costs of staying and of moving north, south, east and west: the negative
+    /// log probabilities of the moves, scaled by `cost_scale`.
+    pub fn move_costs(&self, map: &GameMap, pos: Position) -> [i32; 5] {
+        let logits = self.net.forward(&self.features(map, pos));
+        let max = logits.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let log_sum = max + logits.iter().map(|l| (l - max).exp()).sum::<f64>().ln();
+        let cost = |i: usize| ((log_sum - logits[i]) * self.cost_scale).min(1e9) as i32;
+        [cost(0), cost(3), cost(1), cost(2), cost(4)]
+    }
+}
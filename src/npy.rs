@@ -0,0 +1,379 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+const MAGIC: &[u8] = b"\x93NUMPY";
+
+/// A float array in row-major order.
+#[derive(Debug, Clone)]
+pub struct Array {
+    pub shape: Vec<usize>,
+    pub data: Vec<f64>,
+}
+
+#[derive(Debug)]
+pub enum NpyError {
+    Io(io::Error),
+    /// The file is not something we can read, e.g. a compressed archive.
+    Format(String),
+}
+
+impl fmt::Display for NpyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NpyError::Io(e) => write!(f, "{}", e),
+            NpyError::Format(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for NpyError {}
+
+impl From<io::Error> for NpyError {
+    fn from(e: io::Error) -> Self {
+        NpyError::Io(e)
+    }
+}
+
+fn format_error<T>(message: &str) -> Result<T, NpyError> {
+    Err(NpyError::Format(message.to_string()))
+}
+
+/// `len` bytes at `start`, or an error if the data ends before.
+fn bytes(data: &[u8], start: usize, len: usize) -> Result<&[u8], NpyError> {
+    match start.checked_add(len) {
+        Some(end) if end <= data.len() => Ok(&data[start..end]),
+        _ => format_error("unexpected end of data"),
+    }
+}
+
+fn u16_at(data: &[u8], pos: usize) -> Result<usize, NpyError> {
+    let b = bytes(data, pos, 2)?;
+    Ok(u16::from_le_bytes([b[0], b[1]]) as usize)
+}
+
+fn u32_at(data: &[u8], pos: usize) -> Result<usize, NpyError> {
+    let b = bytes(data, pos, 4)?;
+    Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+}
+
+fn u64_at(data: &[u8], pos: usize) -> Result<usize, NpyError> {
+    let b = bytes(data, pos, 8)?;
+    let mut le = [0; 8];
+    le.copy_from_slice(b);
+    Ok(u64::from_le_bytes(le) as usize)
+}
+
+/// The value of `key` in the header dictionary, e.g. `'<f8'` for `descr`.
+fn header_value<'a>(header: &'a str, key: &str) -> Result<&'a str, NpyError> {
+    let pattern = format!("'{}':", key);
+    let start = match header.find(&pattern) {
+        Some(i) => i + pattern.len(),
+        None => return format_error(&format!("npy header has no {}", key)),
+    };
+    let rest = header[start..].trim_start();
+    let end = if rest.starts_with('(') {
+        rest.find(')').map(|i| i + 1)
+    } else {
+        rest.find(',')
+    };
+    match end {
+        Some(end) => Ok(rest[..end].trim()),
+        None => format_error(&format!("malformed {} in npy header", key)),
+    }
+}
+
+/// Parse the contents of a `.npy` file.
+pub fn parse_npy(data: &[u8]) -> Result<Array, NpyError> {
+    if bytes(data, 0, MAGIC.len())? != MAGIC {
+        return format_error("not an npy file");
+    }
+    let (header_len, header_start) = match bytes(data, 6, 1)?[0] {
+        1 => (u16_at(data, 8)?, 10),
+        2 | 3 => (u32_at(data, 8)?, 12),
+        v => return format_error(&format!("unsupported npy version {}", v)),
+    };
+    let header = String::from_utf8_lossy(bytes(data, header_start, header_len)?);
+
+    if header_value(&header, "fortran_order")? != "False" {
+        return format_error("arrays in Fortran order are not supported");
+    }
+    let shape = header_value(&header, "shape")?
+        .trim_matches(|c| c == '(' || c == ')')
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse::<usize>())
+        .collect::<Result<Vec<_>, _>>()
+        .or_else(|_| format_error("malformed shape in npy header"))?;
+    let len: usize = shape.iter().product();
+
+    let body = header_start + header_len;
+    let descr = header_value(&header, "descr")?.trim_matches('\'');
+    let data = match descr {
+        "<f8" => bytes(data, body, 8 * len)?
+            .chunks(8)
+            .map(|b| {
+                let mut le = [0; 8];
+                le.copy_from_slice(b);
+                f64::from_le_bytes(le)
+            })
+            .collect(),
+        "<f4" => bytes(data, body, 4 * len)?
+            .chunks(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64)
+            .collect(),
+        "<i8" => bytes(data, body, 8 * len)?
+            .chunks(8)
+            .map(|b| {
+                let mut le = [0; 8];
+                le.copy_from_slice(b);
+                i64::from_le_bytes(le) as f64
+            })
+            .collect(),
+        _ => return format_error(&format!("unsupported dtype {}", descr)),
+    };
+    Ok(Array { shape, data })
+}
+
+/// Parse an `.npz` archive into its arrays by name, without the `.npy` extension.
+pub fn parse_npz(data: &[u8]) -> Result<BTreeMap<String, Array>, NpyError> {
+    // the end of central directory record is 22 bytes plus a comment of up to 64k
+    let search_start = data.len().saturating_sub(22 + 0xffff);
+    let eocd = match (search_start..data.len().saturating_sub(21))
+        .rev()
+        .find(|&i| data[i..i + 4] == b"PK\x05\x06"[..])
+    {
+        Some(i) => i,
+        None => return format_error("not an npz archive"),
+    };
+    let n_entries = u16_at(data, eocd + 10)?;
+    let mut pos = u32_at(data, eocd + 16)?;
+
+    let mut arrays = BTreeMap::new();
+    for _ in 0..n_entries {
+        if bytes(data, pos, 4)? != b"PK\x01\x02" {
+            return format_error("corrupt npz directory");
+        }
+        let method = u16_at(data, pos + 10)?;
+        let mut size = u32_at(data, pos + 20)?;
+        let name_len = u16_at(data, pos + 28)?;
+        let extra_len = u16_at(data, pos + 30)?;
+        let comment_len = u16_at(data, pos + 32)?;
+        let mut offset = u32_at(data, pos + 42)?;
+        let name = String::from_utf8_lossy(bytes(data, pos + 46, name_len)?).into_owned();
+
+        // large archives keep sizes and offsets in a zip64 extra field
+        let extra = bytes(data, pos + 46 + name_len, extra_len)?;
+        let mut i = 0;
+        while i + 4 <= extra.len() {
+            let (id, len) = (u16_at(extra, i)?, u16_at(extra, i + 2)?);
+            if id == 1 {
+                let mut field = i + 4;
+                if u32_at(data, pos + 24)? == 0xffff_ffff {
+                    field += 8;
+                }
+                if size == 0xffff_ffff {
+                    size = u64_at(extra, field)?;
+                    field += 8;
+                }
+                if offset == 0xffff_ffff {
+                    offset = u64_at(extra, field)?;
+                }
+            }
+            i += 4 + len;
+        }
+
+        if method != 0 {
+            return format_error(&format!(
+                "{} is compressed; save with np.savez instead of np.savez_compressed",
+                name
+            ));
+        }
+        if bytes(data, offset, 4)? != b"PK\x03\x04" {
+            return format_error("corrupt npz entry");
+        }
+        let start = offset + 30 + u16_at(data, offset + 26)? + u16_at(data, offset + 28)?;
+        let array = parse_npy(bytes(data, start, size)?)
+            .map_err(|e| NpyError::Format(format!("{}: {}", name, e)))?;
+        arrays.insert(name.trim_end_matches(".npy").to_string(), array);
+
+        pos += 46 + name_len + extra_len + comment_len;
+    }
+    Ok(arrays)
+}
+
+/// Load the arrays of an `.npz` archive, or the array of an `.npy` file under its file name.
+///
+/// This is just enough of numpy's formats to exchange arrays with the scripts in
+/// `machine_learning/`: little-endian floats and integers in C order, and archives written by
+/// `np.savez`. Integers are converted to floats.
+pub fn load(path: &str) -> Result<BTreeMap<String, Array>, NpyError> {
+    let data = fs::read(path)?;
+    if path.ends_with(".npy") {
+        let name = Path::new(path)
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut arrays = BTreeMap::new();
+        arrays.insert(name, parse_npy(&data)?);
+        Ok(arrays)
+    } else {
+        parse_npz(&data)
+    }
+}
+
+/// The `.npy` encoding of an array with `body` holding its items of type `descr` in C order.
+fn npy_bytes(descr: &str, shape: &[usize], body: &[u8]) -> Vec<u8> {
+    let dims: Vec<String> = shape.iter().map(|n| n.to_string()).collect();
+    let shape = match dims.len() {
+        1 => format!("({},)", dims[0]),
+        _ => format!("({})", dims.join(", ")),
+    };
+    let mut header = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+        descr, shape
+    );
+    // magic, version and header length take 10 bytes, and numpy aligns the data to 64 bytes
+    while (10 + header.len() + 1) % 64 != 0 {
+        header.push(' ');
+    }
+    header.push('\n');
+
+    let mut out = Vec::with_capacity(10 + header.len() + body.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&[1, 0]);
+    out.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    out.extend_from_slice(header.as_bytes());
+    out.extend_from_slice(body);
+    out
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut table = [0u32; 256];
+    for (i, entry) in table.iter_mut().enumerate() {
+        let mut c = i as u32;
+        for _ in 0..8 {
+            c = if c & 1 == 1 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+        }
+        *entry = c;
+    }
+    !data.iter().fold(!0u32, |c, &b| {
+        table[((c ^ u32::from(b)) & 0xff) as usize] ^ (c >> 8)
+    })
+}
+
+/// Collects arrays and writes them as an uncompressed `.npz` archive, like `np.savez` does.
+#[derive(Default)]
+pub struct NpzWriter {
+    entries: Vec<(String, Vec<u8>)>,
+}
+
+impl NpzWriter {
+    pub fn add_f32(&mut self, name: &str, shape: &[usize], data: &[f32]) {
+        let mut body = Vec::with_capacity(4 * data.len());
+        for v in data {
+            body.extend_from_slice(&v.to_le_bytes());
+        }
+        self.add(name, "<f4", shape, data.len(), body);
+    }
+
+    pub fn add_i64(&mut self, name: &str, shape: &[usize], data: &[i64]) {
+        let mut body = Vec::with_capacity(8 * data.len());
+        for v in data {
+            body.extend_from_slice(&v.to_le_bytes());
+        }
+        self.add(name, "<i8", shape, data.len(), body);
+    }
+
+    /// A one-dimensional array of byte strings, padded to the longest one.
+    pub fn add_strings(&mut self, name: &str, data: &[&str]) {
+        let width = data.iter().map(|s| s.len()).max().unwrap_or(0).max(1);
+        let mut body = Vec::with_capacity(width * data.len());
+        for s in data {
+            body.extend_from_slice(s.as_bytes());
+            body.resize(body.len() + width - s.len(), 0);
+        }
+        let descr = format!("|S{}", width);
+        self.add(name, &descr, &[data.len()], data.len(), body);
+    }
+
+    fn add(&mut self, name: &str, descr: &str, shape: &[usize], len: usize, body: Vec<u8>) {
+        assert_eq!(
+            shape.iter().product::<usize>(),
+            len,
+            "shape {:?} of {} does not fit its data",
+            shape,
+            name
+        );
+        self.entries
+            .push((format!("{}.npy", name), npy_bytes(descr, shape, &body)));
+    }
+
+    /// Write the archive. It has no zip64 records, so it must stay below 4 GB.
+    pub fn write<W: Write>(&self, mut out: W) -> Result<(), NpyError> {
+        let too_large = || NpyError::Format("too much data for an npz archive".to_string());
+        let u32_of = |n: usize| u32::try_from(n).map_err(|_| too_large());
+        // 1980-01-01, the earliest date a zip file can hold
+        let (time, date) = (0u16, 0x21u16);
+
+        let mut data = vec![];
+        let mut directory = vec![];
+        for (name, array) in &self.entries {
+            let offset = u32_of(data.len())?;
+            let size = u32_of(array.len())?;
+            let crc = crc32(array);
+
+            data.extend_from_slice(b"PK\x03\x04");
+            for v in &[20, 0, 0, time, date] {
+                data.extend_from_slice(&v.to_le_bytes());
+            }
+            for v in &[crc, size, size] {
+                data.extend_from_slice(&v.to_le_bytes());
+            }
+            data.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            data.extend_from_slice(&0u16.to_le_bytes());
+            data.extend_from_slice(name.as_bytes());
+            data.extend_from_slice(array);
+
+            directory.extend_from_slice(b"PK\x01\x02");
+            for v in &[20, 20, 0, 0, time, date] {
+                directory.extend_from_slice(&v.to_le_bytes());
+            }
+            for v in &[crc, size, size] {
+                directory.extend_from_slice(&v.to_le_bytes());
+            }
+            for v in &[name.len() as u16, 0, 0, 0, 0] {
+                directory.extend_from_slice(&v.to_le_bytes());
+            }
+            for v in &[0, offset] {
+                directory.extend_from_slice(&v.to_le_bytes());
+            }
+            directory.extend_from_slice(name.as_bytes());
+        }
+
+        let n_entries = self.entries.len() as u16;
+        let mut end = b"PK\x05\x06".to_vec();
+        for v in &[0, 0, n_entries, n_entries] {
+            end.extend_from_slice(&v.to_le_bytes());
+        }
+        for v in &[u32_of(directory.len())?, u32_of(data.len())?] {
+            end.extend_from_slice(&v.to_le_bytes());
+        }
+        end.extend_from_slice(&0u16.to_le_bytes());
+
+        out.write_all(&data)?;
+        out.write_all(&directory)?;
+        out.write_all(&end)?;
+        out.flush()?;
+        Ok(())
+    }
+}
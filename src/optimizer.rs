@@ -0,0 +1,352 @@
+use config::{Config, ConfigError, GameMode};
+use engine::GameSettings;
+use rng::Rng;
+use serde_json::{self, Value};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use tournament::{BotSpec, Tournament};
+use BotOptions;
+
+/// Range of a tuned config value.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Range {
+    pub min: f64,
+    pub max: f64,
+}
+
+/// What to tune and how, read from a JSON file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TuningSpec {
+    /// Config values to tune by their dotted path, e.g. `ships.return_cargo`. Values that are
+    /// integers in the config stay integers.
+    pub parameters: BTreeMap<String, Range>,
+    pub iterations: usize,
+    /// Candidates sampled per iteration.
+    pub population: usize,
+    /// The best candidates of an iteration, which the next iteration samples around.
+    pub elite: usize,
+    /// How far the sampling distribution moves towards the elite in one iteration.
+    pub learning_rate: f64,
+    /// Games each candidate plays against the reference config.
+    pub games: usize,
+    pub size: usize,
+    pub players: usize,
+    pub seed: Option<u64>,
+}
+
+impl Default for TuningSpec {
+    fn default() -> Self {
+        TuningSpec {
+            parameters: BTreeMap::new(),
+            iterations: 10,
+            population: 12,
+            elite: 3,
+            learning_rate: 0.7,
+            games: 20,
+            size: 32,
+            players: 2,
+            seed: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum TuningError {
+    Io(String, io::Error),
+    Parse(serde_json::Error),
+    /// Problems with the spec, one message each.
+    Invalid(Vec<String>),
+    Config(ConfigError),
+    Tournament(io::Error),
+}
+
+impl fmt::Display for TuningError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TuningError::Io(file, e) => write!(f, "can't access {}: {}", file, e),
+            TuningError::Parse(e) => write!(f, "malformed tuning spec: {}", e),
+            TuningError::Invalid(errors) => {
+                write!(f, "invalid tuning spec: {}", errors.join("; "))
+            }
+            TuningError::Config(e) => write!(f, "{}", e),
+            TuningError::Tournament(e) => write!(f, "tournament failed: {}", e),
+        }
+    }
+}
+
+impl Error for TuningError {}
+
+impl TuningSpec {
+    pub fn load(file: &str) -> Result<Self, TuningError> {
+        let text = fs::read_to_string(file).map_err(|e| TuningError::Io(file.to_string(), e))?;
+        serde_json::from_str(&text).map_err(TuningError::Parse)
+    }
+
+    fn mode(&self) -> GameMode {
+        GameMode {
+            players: self.players,
+            width: self.size,
+            height: self.size,
+        }
+    }
+}
+
+/// Config values, by path.
+pub type Params = BTreeMap<String, f64>;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Candidate {
+    pub params: Params,
+    pub win_rate: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Iteration {
+    /// The sampling distribution of this iteration.
+    pub mean: Params,
+    pub std_dev: Params,
+    pub candidates: Vec<Candidate>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TuningResult {
+    /// The mean of the final distribution, with its win rate in a fresh set of games.
+    pub best: Candidate,
+    pub history: Vec<Iteration>,
+}
+
+/// Cross-entropy method over config values.
+///
+/// Every iteration samples candidates from a normal distribution per parameter and lets each
+/// play a tournament against the reference config. The distribution then moves towards the
+/// candidates with the best win rates. All candidates of an iteration play on the same maps
+/// and seats, so their win rates differ by their parameters rather than by luck of the draw.
+///
+/// The result is the mean of the final distribution rather than the best candidate ever
+/// seen: with a few dozen games per candidate, the top win rate of an iteration is mostly
+/// noise.
+pub struct Optimizer {
+    spec: TuningSpec,
+    reference: BotOptions,
+    /// Whether a parameter is an integer in the config.
+    integer: BTreeMap<String, bool>,
+    initial: Params,
+    threads: usize,
+}
+
+impl Optimizer {
+    /// Check the spec and read the starting values from the reference config.
+    pub fn new(
+        spec: TuningSpec,
+        reference: BotOptions,
+        threads: usize,
+    ) -> Result<Self, TuningError> {
+        let mut errors = vec![];
+        if spec.parameters.is_empty() {
+            errors.push("no parameters to tune".to_string());
+        }
+        if spec.elite == 0 || spec.elite > spec.population {
+            errors.push(format!(
+                "elite must be between 1 and the population of {} (got {})",
+                spec.population, spec.elite
+            ));
+        }
+        if spec.learning_rate <= 0.0 || spec.learning_rate > 1.0 {
+            errors.push(format!(
+                "learning_rate must be in (0, 1] (got {})",
+                spec.learning_rate
+            ));
+        }
+        if spec.games == 0 {
+            errors.push("games must be positive".to_string());
+        }
+        let settings = GameSettings {
+            size: spec.size,
+            players: spec.players,
+            seed: 0,
+        };
+        if let Err(e) = settings.validate() {
+            errors.push(e);
+        }
+        if !errors.is_empty() {
+            return Err(TuningError::Invalid(errors));
+        }
+
+        let file = reference.config_file.as_ref().map(String::as_ref);
+        let config =
+            Config::load(file, &spec.mode(), &reference.overrides).map_err(TuningError::Config)?;
+        let config = serde_json::to_value(&config).map_err(TuningError::Parse)?;
+
+        let mut integer = BTreeMap::new();
+        let mut initial = Params::new();
+        for (path, range) in &spec.parameters {
+            if range.min >= range.max {
+                errors.push(format!("{}: min must be below max", path));
+                continue;
+            }
+            let pointer = format!("/{}", path.replace('.', "/"));
+            match config.pointer(&pointer) {
+                Some(Value::Number(n)) => {
+                    integer.insert(path.clone(), n.is_i64() || n.is_u64());
+                    let x = n.as_f64().unwrap();
+                    initial.insert(path.clone(), x.clamp(range.min, range.max));
+                }
+                Some(_) => errors.push(format!("{} is not a number in the config", path)),
+                None => errors.push(format!("{} is not in the config", path)),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(TuningError::Invalid(errors));
+        }
+
+        Ok(Optimizer {
+            spec,
+            reference,
+            integer,
+            initial,
+            threads,
+        })
+    }
+
+    /// `--set` overrides for a candidate.
+    pub fn overrides(&self, params: &Params) -> Vec<String> {
+        params
+            .iter()
+            .map(|(path, &x)| {
+                if self.integer[path] {
+                    format!("{}={}", path, x.round() as i64)
+                } else {
+                    format!("{}={}", path, x)
+                }
+            })
+            .collect()
+    }
+
+    fn sample(&self, mean: &Params, std_dev: &Params, rng: &mut Rng) -> Params {
+        mean.iter()
+            .map(|(path, &m)| {
+                let range = self.spec.parameters[path];
+                let mut x = (m + std_dev[path] * rng.normal()).clamp(range.min, range.max);
+                if self.integer[path] {
+                    x = x.round();
+                }
+                (path.clone(), x)
+            })
+            .collect()
+    }
+
+    fn evaluate(&self, params: &Params, seed: u64) -> Result<f64, TuningError> {
+        let mut candidate = self.reference.clone();
+        candidate.overrides.extend(self.overrides(params));
+        let tournament = Tournament {
+            bots: vec![
+                BotSpec::local("candidate", candidate),
+                BotSpec::local("reference", self.reference.clone()),
+            ],
+            games: self.spec.games,
+            size: self.spec.size,
+            players: self.spec.players,
+            seed,
+            threads: self.threads,
+            sprt: None,
+        };
+        let report = tournament.run(|_| {}).map_err(TuningError::Tournament)?;
+        Ok(report.win_rate)
+    }
+
+    /// Run the search. `progress` sees every iteration as it completes.
+    pub fn run<F: FnMut(&Iteration)>(&self, mut progress: F) -> Result<TuningResult, TuningError> {
+        let seed = self
+            .spec
+            .seed
+            .unwrap_or_else(|| Rng::from_time().next_u64() % 1_000_000_000);
+        let mut rng = Rng::new(seed);
+        let lr = self.spec.learning_rate;
+
+        let mut mean = self.initial.clone();
+        let mut std_dev: Params = self
+            .spec
+            .parameters
+            .iter()
+            .map(|(path, r)| (path.clone(), (r.max - r.min) / 4.0))
+            .collect();
+
+        let mut history = vec![];
+        for i in 0..self.spec.iterations {
+            let games_seed = seed + i as u64;
+            let mut candidates = vec![];
+            for _ in 0..self.spec.population {
+                let params = self.sample(&mean, &std_dev, &mut rng);
+                let win_rate = self.evaluate(&params, games_seed)?;
+                candidates.push(Candidate { params, win_rate });
+            }
+
+            let iteration = Iteration {
+                mean: mean.clone(),
+                std_dev: std_dev.clone(),
+                candidates,
+            };
+            progress(&iteration);
+
+            let mut ranked: Vec<&Candidate> = iteration.candidates.iter().collect();
+            ranked.sort_by(|a, b| b.win_rate.partial_cmp(&a.win_rate).unwrap());
+            let elite = &ranked[..self.spec.elite];
+            let n = elite.len() as f64;
+            for (path, range) in &self.spec.parameters {
+                let m = elite.iter().map(|c| c.params[path]).sum::<f64>() / n;
+                let var = elite
+                    .iter()
+                    .map(|c| (c.params[path] - m).powi(2))
+                    .sum::<f64>()
+                    / n;
+                // a floor keeps the search from collapsing onto a lucky candidate
+                let floor = (range.max - range.min) / 100.0;
+                let s = std_dev.get_mut(path).unwrap();
+                *s = ((1.0 - lr) * *s + lr * var.sqrt()).max(floor);
+                let x = mean.get_mut(path).unwrap();
+                *x = (1.0 - lr) * *x + lr * m;
+            }
+
+            history.push(iteration);
+        }
+
+        let mut params = mean;
+        for (path, x) in params.iter_mut() {
+            if self.integer[path] {
+                *x = x.round();
+            }
+        }
+        let win_rate = self.evaluate(&params, seed + self.spec.iterations as u64)?;
+        Ok(TuningResult {
+            best: Candidate { params, win_rate },
+            history,
+        })
+    }
+
+    /// The full reference config with the tuned values, ready to be used with `--config`.
+    pub fn config(&self, params: &Params) -> Result<Value, TuningError> {
+        let file = self.reference.config_file.as_ref().map(String::as_ref);
+        let mut overrides = self.reference.overrides.clone();
+        overrides.extend(self.overrides(params));
+        let mut config = Config::effective(file, &overrides).map_err(TuningError::Config)?;
+
+        // the values were tuned for this mode, so they must not be shadowed by its profiles
+        for key in self.spec.mode().profile_keys() {
+            for path in params.keys() {
+                let pointer = format!("/profiles/{}/{}", key, path.replace('.', "/"));
+                let tuned = config
+                    .pointer(&format!("/{}", path.replace('.', "/")))
+                    .cloned();
+                if let (Some(value), Some(tuned)) = (config.pointer_mut(&pointer), tuned) {
+                    *value = tuned;
+                }
+            }
+        }
+        Ok(config)
+    }
+}
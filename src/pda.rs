@@ -1,51 +1,271 @@
+use hlt::log::Log;
 
 #[derive(Debug)]
 pub enum StackOp<T> {
     None,
     Done,
     Push(T),
+    /// Swap the top state for a new one, without resuming the state beneath.
+    Replace(T),
     Override(T),
+    /// The state issued an action whose outcome is only known next frame. Like `None`, this
+    /// ends the turn, but instead of being stepped again the state first gets the outcome.
+    Await,
+}
+
+impl<T> StackOp<T> {
+    /// True if the state is finished for this turn.
+    pub fn ends_turn(&self) -> bool {
+        matches!(self, StackOp::None | StackOp::Await)
+    }
+}
+
+/// Whether an awaited action had the intended effect.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Outcome {
+    Confirmed,
+    Failed,
+}
+
+/// Lifecycle notifications sent to states as they move through the stack.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Hook {
+    /// The state was put on the stack.
+    Enter,
+    /// The state is removed from the stack.
+    Exit,
+    /// The state is back on top after the states above it finished.
+    Resume,
+}
+
+/// How long a state has been on the stack.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct StateTime {
+    /// Turns since the state was put on the stack.
+    pub turns: usize,
+    /// Turns in which the state was stepped.
+    pub active_turns: usize,
+}
+
+/// Connects a `StateStack` to the world its states live in.
+pub trait Driver<T> {
+    /// The current turn.
+    fn now(&self) -> usize;
+
+    fn step(&mut self, state: &mut T) -> StackOp<T>;
+
+    fn hook(&mut self, _state: &mut T, _hook: Hook, _time: StateTime) {}
+
+    /// Deliver the outcome of an awaited action.
+    fn outcome(&mut self, _state: &mut T, _outcome: Outcome) -> StackOp<T> {
+        StackOp::None
+    }
+
+    /// Provide a new state when the stack runs empty.
+    fn refill(&mut self) -> Option<T> {
+        None
+    }
+}
+
+/// Protects against states that keep handing control back and forth within one turn.
+const MAX_TRANSITIONS_PER_TURN: usize = 32;
+
+#[derive(Debug)]
+struct Entry<T> {
+    state: T,
+    priority: u32,
+    entered: usize,
+    active_turns: usize,
+    last_active: Option<usize>,
 }
 
 #[derive(Debug)]
 pub struct StateStack<T> {
-    stack: Vec<T>,
+    stack: Vec<Entry<T>>,
+    awaiting: bool,
+    clock: usize,
 }
 
 impl<T> Default for StateStack<T> {
     fn default() -> Self {
-        StateStack { stack: vec![] }
+        StateStack {
+            stack: vec![],
+            awaiting: false,
+            clock: 0,
+        }
     }
 }
 
 impl<T> StateStack<T> {
+    /// Start with an initial state, entered on turn 0. It does not receive `Hook::Enter`.
     pub fn new(task: T) -> Self {
-        StateStack { stack: vec![task] }
+        let mut stack = StateStack::default();
+        let entry = stack.entry(task, 0);
+        stack.stack.push(entry);
+        stack
+    }
+
+    /// Step the top state until one ends the turn, applying all transitions in between.
+    pub fn run<D: Driver<T>>(&mut self, driver: &mut D) {
+        self.clock = driver.now();
+
+        if self.awaiting {
+            return;
+        }
+
+        for _ in 0..MAX_TRANSITIONS_PER_TURN {
+            if self.stack.is_empty() {
+                match driver.refill() {
+                    Some(task) => self.transition(StackOp::Push(task), driver),
+                    None => return,
+                }
+            }
+
+            let clock = self.clock;
+            let op = {
+                let top = self.stack.last_mut().unwrap();
+                if top.last_active != Some(clock) {
+                    top.active_turns += 1;
+                    top.last_active = Some(clock);
+                }
+                driver.step(&mut top.state)
+            };
+
+            let done = op.ends_turn();
+            self.transition(op, driver);
+            if done {
+                return;
+            }
+        }
+
+        Log::log("StateStack: too many transitions in one turn");
     }
 
-    pub fn transition(&mut self, op: StackOp<T>) {
+    pub fn transition<D: Driver<T>>(&mut self, op: StackOp<T>, driver: &mut D) {
+        if let StackOp::None = op {
+            return;
+        }
+        self.clock = driver.now();
+        self.awaiting = false;
         match op {
             StackOp::None => {}
+            StackOp::Await => self.awaiting = !self.stack.is_empty(),
             StackOp::Done => {
-                self.stack.pop();
+                self.pop(driver);
+                let clock = self.clock;
+                if let Some(top) = self.stack.last_mut() {
+                    let time = StateTime {
+                        turns: clock - top.entered,
+                        active_turns: top.active_turns,
+                    };
+                    driver.hook(&mut top.state, Hook::Resume, time);
+                }
+            }
+            StackOp::Push(task) => {
+                let priority = self.priority();
+                self.enter(task, priority, driver);
+            }
+            StackOp::Replace(task) => {
+                let priority = self.pop(driver).unwrap_or(0);
+                self.enter(task, priority, driver);
             }
-            StackOp::Push(task) => self.stack.push(task),
             StackOp::Override(task) => {
-                self.stack.clear();
-                self.stack.push(task);
+                while !self.stack.is_empty() {
+                    self.pop(driver);
+                }
+                self.enter(task, 0, driver);
             }
         }
     }
 
-    pub fn push(&mut self, item: T) {
-        self.stack.push(item)
+    /// Put a state on top if its priority is higher than that of the current top state.
+    /// States pushed by an interrupting state inherit its priority.
+    pub fn interrupt<D: Driver<T>>(&mut self, task: T, priority: u32, driver: &mut D) -> bool {
+        if !self.stack.is_empty() && priority <= self.priority() {
+            return false;
+        }
+        self.clock = driver.now();
+        self.awaiting = false;
+        self.enter(task, priority, driver);
+        true
     }
 
-    pub fn top(&mut self) -> Option<&mut T> {
-        self.stack.last_mut()
+    /// Remove an interrupt of at least `priority`, with the states it pushed, and resume the
+    /// task beneath it.
+    pub fn cancel<D: Driver<T>>(&mut self, priority: u32, driver: &mut D) {
+        if self.priority() < priority {
+            return;
+        }
+        self.clock = driver.now();
+        self.awaiting = false;
+        while self.priority() >= priority && !self.stack.is_empty() {
+            self.pop(driver);
+        }
+        let clock = self.clock;
+        if let Some(top) = self.stack.last_mut() {
+            let time = StateTime {
+                turns: clock - top.entered,
+                active_turns: top.active_turns,
+            };
+            driver.hook(&mut top.state, Hook::Resume, time);
+        }
+    }
+
+    /// Hand the outcome to the awaiting state and apply its response.
+    pub fn resolve<D: Driver<T>>(&mut self, outcome: Outcome, driver: &mut D) {
+        if !self.awaiting {
+            return;
+        }
+        self.clock = driver.now();
+        self.awaiting = false;
+        let top = self.stack.last_mut().expect("awaiting state");
+        let op = driver.outcome(&mut top.state, outcome);
+        self.transition(op, driver);
+    }
+
+    /// True if the top state waits for the outcome of its last action.
+    pub fn is_awaiting(&self) -> bool {
+        self.awaiting
+    }
+
+    /// Priority of the top state, 0 for an empty stack.
+    pub fn priority(&self) -> u32 {
+        self.stack.last().map(|e| e.priority).unwrap_or(0)
     }
 
     pub fn is_empty(&self) -> bool {
         self.stack.is_empty()
     }
+
+    pub fn top(&mut self) -> Option<&mut T> {
+        self.stack.last_mut().map(|e| &mut e.state)
+    }
+
+    fn entry(&self, state: T, priority: u32) -> Entry<T> {
+        Entry {
+            state,
+            priority,
+            entered: self.clock,
+            active_turns: 0,
+            last_active: None,
+        }
+    }
+
+    fn enter<D: Driver<T>>(&mut self, task: T, priority: u32, driver: &mut D) {
+        let entry = self.entry(task, priority);
+        self.stack.push(entry);
+        let top = self.stack.last_mut().unwrap();
+        driver.hook(&mut top.state, Hook::Enter, StateTime::default());
+    }
+
+    /// Remove the top state and return its priority.
+    fn pop<D: Driver<T>>(&mut self, driver: &mut D) -> Option<u32> {
+        let mut entry = self.stack.pop()?;
+        let time = StateTime {
+            turns: self.clock - entry.entered,
+            active_turns: entry.active_turns,
+        };
+        driver.hook(&mut entry.state, Hook::Exit, time);
+        Some(entry.priority)
+    }
 }
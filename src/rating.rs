@@ -0,0 +1,198 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// Expected score of a player `elo` points stronger than its opponent.
+pub fn expected_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+/// Elo difference that corresponds to an expected score.
+pub fn elo_from_score(score: f64) -> f64 {
+    let score = score.clamp(1e-3, 1.0 - 1e-3);
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+pub enum SprtDecision {
+    Continue,
+    /// The bot is not better than `elo0`.
+    AcceptH0,
+    /// The bot is better than `elo1`.
+    AcceptH1,
+}
+
+/// Sequential probability ratio test of H0: "the first bot is `elo0` stronger than its
+/// opponents" against H1: "it is `elo1` stronger".
+///
+/// Game scores are the fraction of opponents a bot finished ahead of, so 4 player games count
+/// like partial wins. The test stops as soon as the log likelihood ratio leaves the interval
+/// given by the error rates `alpha` (accepting H1 when H0 is true) and `beta` (the reverse).
+#[derive(Debug, Copy, Clone, Serialize)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+#[derive(Debug, Copy, Clone, Serialize)]
+pub struct SprtStatus {
+    pub llr: f64,
+    pub lower_bound: f64,
+    pub upper_bound: f64,
+    pub decision: SprtDecision,
+}
+
+impl Sprt {
+    /// Test state after `games` games in which the bot scored `score` in total.
+    pub fn status(&self, score: f64, games: usize) -> SprtStatus {
+        let p0 = expected_score(self.elo0);
+        let p1 = expected_score(self.elo1);
+        let llr = score * (p1 / p0).ln() + (games as f64 - score) * ((1.0 - p1) / (1.0 - p0)).ln();
+        let lower_bound = (self.beta / (1.0 - self.alpha)).ln();
+        let upper_bound = ((1.0 - self.beta) / self.alpha).ln();
+
+        let decision = if llr >= upper_bound {
+            SprtDecision::AcceptH1
+        } else if llr <= lower_bound {
+            SprtDecision::AcceptH0
+        } else {
+            SprtDecision::Continue
+        };
+
+        SprtStatus {
+            llr,
+            lower_bound,
+            upper_bound,
+            decision,
+        }
+    }
+}
+
+impl fmt::Display for SprtStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let verdict = match self.decision {
+            SprtDecision::Continue => "undecided",
+            SprtDecision::AcceptH0 => "H0 accepted",
+            SprtDecision::AcceptH1 => "H1 accepted",
+        };
+        write!(
+            f,
+            "LLR {:.2} ({:.2} .. {:.2}), {}",
+            self.llr, self.lower_bound, self.upper_bound, verdict
+        )
+    }
+}
+
+/// Points a rating moves at most after a game against one opponent.
+const K_FACTOR: f64 = 16.0;
+
+const INITIAL_RATING: f64 = 1500.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rating {
+    pub elo: f64,
+    pub games: usize,
+    /// Games in which the bot finished first.
+    pub wins: usize,
+}
+
+impl Default for Rating {
+    fn default() -> Self {
+        Rating {
+            elo: INITIAL_RATING,
+            games: 0,
+            wins: 0,
+        }
+    }
+}
+
+/// Running Elo ratings of bots, e.g. of the `submissions/vN` versions, kept in a JSON file.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RatingTable {
+    pub bots: BTreeMap<String, Rating>,
+}
+
+impl RatingTable {
+    /// Load the table from `path`, or start a new one if the file does not exist yet.
+    pub fn load(path: &str) -> io::Result<Self> {
+        if !Path::new(path).exists() {
+            return Ok(RatingTable::default());
+        }
+        let file = BufReader::new(File::open(path)?);
+        serde_json::from_reader(file).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut file, self)?;
+        writeln!(file)
+    }
+
+    /// Update the ratings with the ranks of one game, in which `names[i]` finished at
+    /// `ranks[i]`. Every pair of different bots counts as one match, with a tie worth half a
+    /// win, scaled so that a game moves a rating by at most `K_FACTOR`. A bot that fills
+    /// several seats plays one game, which it wins if any of its seats finished first.
+    pub fn record_game(&mut self, names: &[&str], ranks: &[usize]) {
+        let elos: Vec<f64> = names
+            .iter()
+            .map(|name| self.bots.get(*name).map_or(INITIAL_RATING, |r| r.elo))
+            .collect();
+        let k = K_FACTOR / (names.len() - 1) as f64;
+
+        let mut deltas = vec![0.0; names.len()];
+        for i in 0..names.len() {
+            for j in 0..names.len() {
+                if i == j || names[i] == names[j] {
+                    continue;
+                }
+                let score = match ranks[i].cmp(&ranks[j]) {
+                    Ordering::Less => 1.0,
+                    Ordering::Equal => 0.5,
+                    Ordering::Greater => 0.0,
+                };
+                deltas[i] += k * (score - expected_score(elos[i] - elos[j]));
+            }
+        }
+
+        for (i, name) in names.iter().enumerate() {
+            self.bots.entry(name.to_string()).or_default().elo += deltas[i];
+        }
+
+        let mut best = BTreeMap::new();
+        for (name, &rank) in names.iter().zip(ranks) {
+            let r = best.entry(*name).or_insert(rank);
+            *r = rank.min(*r);
+        }
+        for (name, rank) in best {
+            let rating = self.bots.get_mut(name).unwrap();
+            rating.games += 1;
+            if rank == 1 {
+                rating.wins += 1;
+            }
+        }
+    }
+}
+
+impl fmt::Display for RatingTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut bots: Vec<_> = self.bots.iter().collect();
+        bots.sort_by(|a, b| b.1.elo.partial_cmp(&a.1.elo).unwrap());
+        writeln!(f, "{:>6} {:>7} {:>6}  bot", "elo", "games", "wins")?;
+        for (name, r) in bots {
+            writeln!(
+                f,
+                "{:>6.0} {:>7} {:>5.0}%  {}",
+                r.elo,
+                r.games,
+                100.0 * r.wins as f64 / r.games.max(1) as f64,
+                name
+            )?;
+        }
+        Ok(())
+    }
+}
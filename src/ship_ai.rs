@@ -1,15 +1,44 @@
 use ai_manager::AiManager;
+use behavior_tree::BtNode;
 use hlt::direction::Direction;
 use hlt::log::Log;
 use hlt::map_cell::Structure;
+use hlt::position::Position;
 use hlt::ShipId;
-use pda::{StackOp, StateStack};
+use pda::{Driver, Hook, Outcome, StackOp, StateStack, StateTime};
+use utility::{MoveContext, MoveScores};
 use GameState;
 
-#[derive(Debug)]
+/// Priority of a dropoff construction interrupt. Ships already busy with a task of at least
+/// this priority are not considered as builders.
+pub const BUILD_DROPOFF_PRIORITY: u32 = 1;
+
+/// What a ship was built for, decided by the commander at spawn time.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ShipRole {
+    Opening,
+    Regular,
+}
+
+/// Controls a single ship.
+///
+/// Ships are either driven by a pushdown automaton that asks the commander for new tasks, or
+/// by a behavior tree. In both cases, tasks from the commander (interrupts and overrides) go
+/// on the state stack and take precedence over the tree.
 pub struct ShipAi {
     id: ShipId,
     states: StateStack<Box<dyn ShipAiState>>,
+    tree: Option<Box<dyn BtNode<GameState>>>,
+}
+
+impl std::fmt::Debug for ShipAi {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ShipAi")
+            .field("id", &self.id)
+            .field("states", &self.states)
+            .field("tree", &self.tree.is_some())
+            .finish()
+    }
 }
 
 impl ShipAi {
@@ -17,45 +46,215 @@ impl ShipAi {
         ShipAi {
             id,
             states: StateStack::default(),
+            tree: None,
+        }
+    }
+
+    pub fn with_tree(id: ShipId, tree: Box<dyn BtNode<GameState>>) -> Self {
+        ShipAi {
+            id,
+            states: StateStack::default(),
+            tree: Some(tree),
         }
     }
 
     pub fn think(&mut self, aimgr: &AiManager, world: &mut GameState) {
-        loop {
-            if self.states.is_empty() {
-                let op = StackOp::Push(aimgr.commander().request_task(self.id, world));
-                self.states.transition(op);
+        if self.states.is_awaiting() {
+            // any surprise concerning this ship means the awaited action did not work out
+            let outcome = if world.expectations.for_ship(self.id).next().is_some() {
+                Outcome::Failed
+            } else {
+                Outcome::Confirmed
+            };
+            self.resolve(outcome, aimgr, world);
+        }
+
+        self.run(aimgr, world);
+
+        // an action that could not even be issued fails at once, so the commander can re-plan
+        // and the ship carries on with its previous task in this turn
+        if self.states.is_awaiting() {
+            if let Some(outcome) = self.states.top().and_then(|state| state.known_outcome()) {
+                self.resolve(outcome, aimgr, world);
+                self.run(aimgr, world);
+            }
+        }
+    }
+
+    fn run(&mut self, aimgr: &AiManager, world: &mut GameState) {
+        match self.tree {
+            None => self.states.run(&mut ShipDriver::new(self.id, aimgr, world)),
+            Some(ref mut tree) => {
+                let mut driver = ShipDriver::new(self.id, aimgr, world);
+                driver.refill = false;
+                self.states.run(&mut driver);
+                if self.states.is_empty() {
+                    tree.tick(world);
+                }
             }
+        }
+    }
 
-            let op = self.states.top().unwrap().step(self.id, world);
-            if let StackOp::None = op {
-                break
-            } else {
-                self.states.transition(op);
+    /// Report the outcome of the awaited action to the ship's current state and the commander.
+    pub fn resolve(&mut self, outcome: Outcome, aimgr: &AiManager, world: &mut GameState) {
+        if !self.states.is_awaiting() {
+            return;
+        }
+        let id = self.id;
+        Log::log(&format!("{:?}: action outcome {:?}", id, outcome));
+        self.states
+            .resolve(outcome, &mut ShipDriver::new(id, aimgr, world));
+        aimgr.commander().notify_outcome(id, outcome, aimgr, world);
+    }
+
+    /// Pre-empt the current task if it has a lower priority. Returns false if the ship is busy
+    /// with something more important.
+    pub fn interrupt(
+        &mut self,
+        task: Box<dyn ShipAiState>,
+        priority: u32,
+        aimgr: &AiManager,
+        world: &mut GameState,
+    ) -> bool {
+        self.states
+            .interrupt(task, priority, &mut ShipDriver::new(self.id, aimgr, world))
+    }
+
+    /// Drop an interrupt of at least `priority` and go back to the task it pre-empted.
+    pub fn cancel_interrupt(&mut self, priority: u32, aimgr: &AiManager, world: &mut GameState) {
+        self.states
+            .cancel(priority, &mut ShipDriver::new(self.id, aimgr, world))
+    }
+
+    pub fn override_task(
+        &mut self,
+        task: Box<dyn ShipAiState>,
+        aimgr: &AiManager,
+        world: &mut GameState,
+    ) {
+        self.states.transition(
+            StackOp::Override(task),
+            &mut ShipDriver::new(self.id, aimgr, world),
+        );
+    }
+
+    pub fn priority(&self) -> u32 {
+        self.states.priority()
+    }
+
+    /// True while the ship waits for the outcome of its last action.
+    pub fn is_awaiting(&self) -> bool {
+        self.states.is_awaiting()
+    }
+}
+
+struct ShipDriver<'a> {
+    id: ShipId,
+    aimgr: &'a AiManager,
+    world: &'a mut GameState,
+    refill: bool,
+}
+
+impl<'a> ShipDriver<'a> {
+    fn new(id: ShipId, aimgr: &'a AiManager, world: &'a mut GameState) -> Self {
+        ShipDriver {
+            id,
+            aimgr,
+            world,
+            refill: true,
+        }
+    }
+}
+
+impl<'a> Driver<Box<dyn ShipAiState>> for ShipDriver<'a> {
+    fn now(&self) -> usize {
+        self.world.game.turn_number
+    }
+
+    fn step(&mut self, state: &mut Box<dyn ShipAiState>) -> StackOp<Box<dyn ShipAiState>> {
+        self.world.note_state(self.id, state.name());
+        state.step(self.id, self.world)
+    }
+
+    fn hook(&mut self, state: &mut Box<dyn ShipAiState>, hook: Hook, time: StateTime) {
+        match hook {
+            Hook::Enter => state.on_enter(self.id, self.world),
+            Hook::Exit => {
+                Log::log(&format!(
+                    "{:?} leaves {:?} after {} turns, {} of them active",
+                    self.id, state, time.turns, time.active_turns
+                ));
+                state.on_exit(self.id, self.world)
             }
+            Hook::Resume => state.on_resume(self.id, self.world),
         }
     }
 
-    pub fn push_task(&mut self, task: Box<dyn ShipAiState>) {
-        self.states.push(task);
+    fn outcome(
+        &mut self,
+        state: &mut Box<dyn ShipAiState>,
+        outcome: Outcome,
+    ) -> StackOp<Box<dyn ShipAiState>> {
+        state.on_outcome(outcome, self.id, self.world)
+    }
+
+    fn refill(&mut self) -> Option<Box<dyn ShipAiState>> {
+        if self.refill {
+            Some(self.aimgr.commander().request_task(self.id, self.world))
+        } else {
+            None
+        }
     }
 }
 
 pub trait ShipAiState: std::fmt::Debug {
+    fn name(&self) -> &'static str;
+
     fn step(&mut self, id: ShipId, world: &mut GameState) -> StackOp<Box<dyn ShipAiState>>;
+
+    fn on_enter(&mut self, _id: ShipId, _world: &mut GameState) {}
+
+    fn on_exit(&mut self, _id: ShipId, _world: &mut GameState) {}
+
+    /// Called when the state is back on top after the states pushed above it are done.
+    fn on_resume(&mut self, _id: ShipId, _world: &mut GameState) {}
+
+    /// The outcome of the awaited action if it is known right away, e.g. because the action
+    /// could not be issued at all.
+    fn known_outcome(&self) -> Option<Outcome> {
+        None
+    }
+
+    /// Called on the next frame after `step` returned `StackOp::Await`, or in the same turn
+    /// if `known_outcome` gives the outcome.
+    fn on_outcome(
+        &mut self,
+        _outcome: Outcome,
+        _id: ShipId,
+        _world: &mut GameState,
+    ) -> StackOp<Box<dyn ShipAiState>> {
+        StackOp::None
+    }
 }
 
 #[derive(Debug)]
 pub struct Collect;
 
 impl ShipAiState for Collect {
+    fn name(&self) -> &'static str {
+        "Collect"
+    }
+
     fn step(&mut self, id: ShipId, world: &mut GameState) -> StackOp<Box<dyn ShipAiState>> {
         let pos = world.get_ship(id).position;
         let cargo = world.get_ship(id).halite as i32;
         let phi0 = world.get_pheromone(pos);
         Log::log(&format!("{:?} observes a pheromone level of {} at {:?}", id, phi0, pos));
 
-        if cargo >= 950 || phi0 < 10.0 && cargo >= 500 {
+        let cfg = &world.config.ships;
+        if cargo >= cfg.return_cargo
+            || phi0 < cfg.early_return_pheromone && cargo >= cfg.early_return_cargo
+        {
             return StackOp::Done
         }
 
@@ -63,109 +262,66 @@ impl ShipAiState for Collect {
             return StackOp::None
         }
 
-        let dist = world.get_return_distance(world.get_ship(id).position);
-        if world.rounds_left()
-            <= dist
-            + (world.me().ship_ids.len() * world.config.navigation.go_home_safety_factor)
-            / (1 * (1 + world.me().dropoff_ids.len()))
-        {
-            return StackOp::Override(Box::new(GoHome))
-        }
-
-        let mc = world.movement_cost(&pos);
-
-        let current_halite = world.halite_gain(&pos) * world.game.constants.extract_ratio; // factor inspiration into current_halite
+        let ctx = MoveContext::observe(id, world);
+        let scores = MoveScores::evaluate(&ctx, &world.config.ships.utility);
+        Log::log(&scores.explain(id));
 
-        let mut weights: Vec<_> = if cargo < mc {
-            vec![9999999.0, 0.0, 0.0, 0.0, 0.0]
-        } else {
-            Direction::get_all_options()
-                .into_iter()
-                .map(|d| pos.directional_offset(d))
-                .map(|p| world.get_pheromone(p))
-                .collect()
-        };
-
-        if current_halite < 1
-            && weights[0] < 1.0
-            && weights[1] < 1.0
-            && weights[2] < 1.0
-            && weights[3] < 1.0
-        {
-            weights[4] = -9999999.0; // no loitering on empty cells
-            let [c0, cn, cs, ce, cw] = world.get_return_dir_costs(pos);
-            weights[0] += 0.1 * (cw - c0) as f64;
-            weights[1] += 0.1 * (ce - c0) as f64;
-            weights[2] += 0.1 * (cn - c0) as f64;
-            weights[3] += 0.1 * (cs - c0) as f64;
-        } else if world.game.map.at_position(&pos).structure != Structure::None {
-            weights[4] = -9999999.0; // no loitering at the shipyard
-        } else if current_halite > world.config.ships.greedy_harvest_limit && phi0 < 1000.0 {
-            weights[4] = 1000.0 + current_halite as f64;
-        } else if current_halite as f64 > phi0 {
-            weights[4] = current_halite as f64;
+        if ctx.has_prey() {
+            // attract nearby ships a bit more
+            let attraction = world.config.pheromones.prey_attraction;
+            world.add_pheromone(pos, attraction);
         }
 
-        let mut costs: Vec<_> = Direction::get_all_options()
-            .into_iter()
-            .map(|d| pos.directional_offset(d))
-            .map(|p| if cargo <= world.config.ships.carefulness_limit {world.mp.is_occupied(p)} else {world.mp.is_reachable(p)})
-            .zip(weights)
-            .map(|(avoid, w)| if avoid {i32::max_value() - 10} else {-(w * 100.0) as i32})
-            .collect();
+        let [cw, ce, cn, cs, c0] = scores.costs();
+        world.gns.plan_move(id, pos, c0, cn, cs, ce, cw);
 
-        let mut prey = vec![];
-        for p in Direction::get_all_cardinals().into_iter().map(|d| pos.directional_offset(d)) {
-            prey.push(None);
-            if let Some(ship) = world.get_ship_at(p) {
-                if ship.owner == world.game.my_id {
-                    continue
-                }
+        StackOp::None
+    }
+}
 
-                let other_cargo = ship.halite as i32;
-                if other_cargo <= cargo {
-                    continue
-                }
+/// Like `Collect`, but the learned policy chooses the moves.
+#[derive(Debug)]
+pub struct LearnedCollect;
 
-                let r = world
-                    .find_nearest_oponent(p, true)
-                    .map(|id| world.get_ship(id).position)
-                    .map(|sp| world.game.map.calculate_distance(&p, &sp))
-                    .unwrap_or(10);
-
-                Log::log(&format!("potential prey at {:?} with nearest opponent {} steps away...", p, r));
-
-                let free_cargo = world.my_ships()
-                    .map(|id| world.get_ship(id))
-                    .filter(|ship| ship.position != pos)
-                    .filter(|ship| world.game.map.calculate_distance(&p, &ship.position) <= r)
-                    .inspect(|ship| Log::log(&format!("   ... and friendly ship at {:?}", ship.position)))
-                    .map(|ship| ship.capacity() as i32)
-                    .sum::<i32>();
-
-                if free_cargo > cargo {
-                    let aggressiveness = if world.game.players.len() == 2 {
-                        1000
-                    } else {
-                        10
-                    };
-                    *prey.last_mut().unwrap() = Some(aggressiveness * (other_cargo - cargo) as i32);
-                }
-            }
+impl ShipAiState for LearnedCollect {
+    fn name(&self) -> &'static str {
+        "LearnedCollect"
+    }
+
+    fn step(&mut self, id: ShipId, world: &mut GameState) -> StackOp<Box<dyn ShipAiState>> {
+        let pos = world.get_ship(id).position;
+        let cargo = world.get_ship(id).halite as i32;
+        if cargo >= world.config.ships.return_cargo {
+            return StackOp::Done;
         }
 
-        for (c, &pr) in costs.iter_mut().zip(&prey) {
-            if let Some(gain) = pr {
-                *c = -gain;
-            }
+        if stuck_move(id, world) {
+            return StackOp::None;
         }
 
-        if prey.into_iter().any(|pr| pr.is_some()) {
-            // attract nearby ships a bit more
-            world.add_pheromone(pos, 1000.0);
+        let mut costs = world
+            .policy
+            .as_ref()
+            .expect("ships only collect with a policy if one is loaded")
+            .move_costs(&world.game.map, pos);
+        Log::log(&format!("{:?} policy costs: {:?}", id, costs));
+
+        // the network only knows about halite, so keep it out of reach of opponents
+        let options = [
+            Direction::Still,
+            Direction::North,
+            Direction::South,
+            Direction::East,
+            Direction::West,
+        ];
+        for (c, &d) in costs.iter_mut().zip(&options) {
+            if world.mp.is_reachable(pos.directional_offset(d)) {
+                *c = i32::MAX - 10;
+            }
         }
 
-        world.gns.plan_move(id, pos, costs[4], costs[2], costs[3], costs[1], costs[0]);
+        let [c0, cn, cs, ce, cw] = costs;
+        world.gns.plan_move(id, pos, c0, cn, cs, ce, cw);
 
         StackOp::None
     }
@@ -183,9 +339,14 @@ impl Deliver {
 }
 
 impl ShipAiState for Deliver {
+    fn name(&self) -> &'static str {
+        "Deliver"
+    }
+
     fn step(&mut self, id: ShipId, world: &mut GameState) -> StackOp<Box<dyn ShipAiState>> {
         if world.get_ship(id).halite <= 0 {
-            world.notify_return(self.turns_taken);
+            let rate = world.config.statistics.return_length_update_rate;
+            world.statistics.record_return(id, self.turns_taken, rate);
             return StackOp::Done;
         }
 
@@ -236,6 +397,10 @@ impl ShipAiState for Deliver {
 pub struct GoHome;
 
 impl ShipAiState for GoHome {
+    fn name(&self) -> &'static str {
+        "GoHome"
+    }
+
     fn step(&mut self, id: ShipId, world: &mut GameState) -> StackOp<Box<dyn ShipAiState>> {
         let pos = world.get_ship(id).position;
 
@@ -289,19 +454,103 @@ impl ShipAiState for GoHome {
 }
 
 #[derive(Debug)]
-pub struct BuildDropoff;
+pub struct GoBuildDropoff {
+    site: Position,
+}
+
+impl GoBuildDropoff {
+    pub fn new(site: Position) -> Self {
+        GoBuildDropoff { site }
+    }
+}
+
+impl ShipAiState for GoBuildDropoff {
+    fn name(&self) -> &'static str {
+        "GoBuildDropoff"
+    }
+
+    fn step(&mut self, id: ShipId, world: &mut GameState) -> StackOp<Box<dyn ShipAiState>> {
+        let pos = world.get_ship(id).position;
+
+        if pos == world.game.map.normalize(&self.site) {
+            // if building fails we continue with whatever we did before the trip
+            return StackOp::Replace(Box::new(BuildDropoff::new()));
+        }
+
+        if stuck_move(id, world) {
+            return StackOp::None;
+        }
+
+        let costs = world.get_dijkstra_move(pos, self.site);
+
+        let costs: Vec<_> = Direction::get_all_options()
+            .into_iter()
+            .map(|d| pos.directional_offset(d))
+            .zip(costs.iter())
+            .map(|(p, &c)| {
+                if world.mp.is_reachable(p) {
+                    i32::MAX - 10
+                } else {
+                    c
+                }
+            })
+            .collect();
+
+        world
+            .gns
+            .plan_move(id, pos, costs[4], costs[2], costs[3], costs[1], costs[0]);
+
+        StackOp::None
+    }
+}
+
+#[derive(Debug)]
+pub struct BuildDropoff {
+    unaffordable: bool,
+}
+
+impl BuildDropoff {
+    pub fn new() -> Self {
+        BuildDropoff {
+            unaffordable: false,
+        }
+    }
+}
 
 impl ShipAiState for BuildDropoff {
+    fn name(&self) -> &'static str {
+        "BuildDropoff"
+    }
+
     fn step(&mut self, id: ShipId, world: &mut GameState) -> StackOp<Box<dyn ShipAiState>> {
-        // todo: move a few steps up the density gradient
         if world.try_build_dropoff(id) {
             Log::log(&format!("{:?} building dropoff", id));
-            StackOp::None
         } else {
-            // if it fails the commander will eventually tell us to try again. Otherwise, continue with previous task
-            Log::log(&format!("{:?} failed to build dropoff", id));
-            StackOp::Done
+            // we cannot afford it; report the failure so the commander can decide what to do
+            Log::log(&format!("{:?} cannot afford the dropoff", id));
+            self.unaffordable = true;
         }
+        StackOp::Await
+    }
+
+    fn known_outcome(&self) -> Option<Outcome> {
+        if self.unaffordable {
+            Some(Outcome::Failed)
+        } else {
+            None
+        }
+    }
+
+    fn on_outcome(
+        &mut self,
+        outcome: Outcome,
+        id: ShipId,
+        _world: &mut GameState,
+    ) -> StackOp<Box<dyn ShipAiState>> {
+        // the commander was notified as well. A confirmed dropoff means the ship is gone, and
+        // after a failure the commander decides whether to retry, so either way we are done.
+        Log::log(&format!("{:?} dropoff construction: {:?}", id, outcome));
+        StackOp::Done
     }
 }
 
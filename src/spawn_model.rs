@@ -0,0 +1,85 @@
+use commander::{enough_rounds_for_spawn, halite_left_per_ship};
+use hlt::log::Log;
+use GameState;
+
+/// Expected halite a ship spawned this turn brings home until the end of the game.
+pub struct SpawnEstimate {
+    /// Halite returned per ship and turn, averaged over the statistics window.
+    pub rate: f64,
+    /// Turns the new ship can spend collecting.
+    pub productive_turns: usize,
+    /// Halite left on the map.
+    pub halite_left: usize,
+    /// All ships on the map, including the new one.
+    pub n_ships: usize,
+    pub expected_return: f64,
+}
+
+impl SpawnEstimate {
+    /// Returns `None` while there are too few observations to estimate a collection rate.
+    ///
+    /// Collecting depletes the map, so the rate of every ship falls in proportion to the
+    /// halite left. With all ships collecting at rate `r` from `H` halite, a single ship
+    /// returns `H / N * (1 - exp(-r * N * T / H))` within `T` turns. This is close to `r * T`
+    /// early on and approaches the ship's share of the remaining halite when the map runs dry.
+    pub fn new(world: &GameState) -> Option<Self> {
+        let window = world.config.statistics.halite_collection_window;
+        let samples = world.statistics.collection_rate();
+        let samples = &samples[samples.len().saturating_sub(window)..];
+        if samples.len() < world.config.strategy.spawn_min_samples {
+            return None;
+        }
+        let rate = samples.iter().sum::<f64>() / samples.len() as f64;
+
+        let productive_turns = world
+            .rounds_left()
+            .saturating_sub(world.config.strategy.spawn_ramp_up_turns);
+        let halite_left: usize = world.game.map.iter().map(|cell| cell.halite).sum();
+        let n_ships = world.game.ships.len() + 1;
+
+        let share = halite_left as f64 / n_ships as f64;
+        let expected_return = if halite_left == 0 || rate <= 0.0 {
+            0.0
+        } else {
+            let exhaustion = rate * n_ships as f64 * productive_turns as f64 / halite_left as f64;
+            share * (1.0 - (-exhaustion).exp())
+        };
+
+        Some(SpawnEstimate {
+            rate,
+            productive_turns,
+            halite_left,
+            n_ships,
+            expected_return,
+        })
+    }
+
+    pub fn is_profitable(&self, world: &GameState) -> bool {
+        self.expected_return
+            > (world.game.constants.ship_cost + world.config.strategy.spawn_margin) as f64
+    }
+}
+
+/// Spawn if a new ship is expected to return more than it costs.
+///
+/// Falls back to comparing the halite left per ship with the ship cost until enough
+/// collection statistics are available.
+pub fn want_ship(world: &GameState) -> bool {
+    match SpawnEstimate::new(world) {
+        Some(estimate) => {
+            Log::log(&format!(
+                "Spawn model: rate {:.2} over {} turns, {} halite left for {} ships -> expected return {:.0}",
+                estimate.rate,
+                estimate.productive_turns,
+                estimate.halite_left,
+                estimate.n_ships,
+                estimate.expected_return
+            ));
+            estimate.is_profitable(world)
+        }
+        None => {
+            halite_left_per_ship(world) > world.game.constants.ship_cost
+                && enough_rounds_for_spawn(world)
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use events::GameEvent;
+use hlt::game::Game;
+use hlt::log::Log;
+use hlt::map_cell::Structure;
+use hlt::position::Position;
+use hlt::ShipId;
+use serde_json;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::Write;
+
+/// What happened to our fleet during one turn.
+#[derive(Default, Serialize)]
+pub struct TurnRecord {
+    pub turn: usize,
+    pub halite: usize,
+    pub n_ships: usize,
+    pub halite_left: usize,
+    pub mined: usize,
+    pub delivered: usize,
+    pub burned: usize,
+    pub lost_in_collisions: usize,
+    pub ships_lost: usize,
+    pub idle_ships: usize,
+}
+
+/// Life story of one of our ships.
+#[derive(Default, Serialize)]
+pub struct ShipRecord {
+    pub spawned: usize,
+    pub removed: Option<usize>,
+    pub became_dropoff: bool,
+    pub mined: usize,
+    pub delivered: usize,
+    pub burned: usize,
+    pub lost_in_collision: usize,
+    pub idle_turns: usize,
+    pub return_trips: Vec<usize>,
+}
+
+#[derive(Serialize)]
+struct Totals {
+    mined: usize,
+    delivered: usize,
+    burned: usize,
+    lost_in_collisions: usize,
+    idle_turns: usize,
+    ships_built: usize,
+    ships_lost: usize,
+    dropoffs_built: usize,
+    spent: usize,
+}
+
+#[derive(Serialize)]
+struct Summary<'a> {
+    player: usize,
+    turns_played: usize,
+    final_halite: usize,
+    totals: Totals,
+    collection_rate: &'a [f64],
+    turns: &'a [TurnRecord],
+    ships: &'a BTreeMap<usize, ShipRecord>,
+}
+
+#[derive(Clone, Copy)]
+struct ShipSnapshot {
+    pos: Position,
+    cargo: usize,
+    cell_halite: usize,
+}
+
+/// Records per-turn and per-ship time series of our fleet's performance.
+///
+/// Everything is derived by comparing consecutive frames, so it also reflects what the server
+/// actually did rather than what we asked for.
+#[derive(Serialize)]
+pub struct Statistics {
+    turns: Vec<TurnRecord>,
+    ships: BTreeMap<usize, ShipRecord>,
+    collection_rate: Vec<f64>,
+    halite_percentiles: Vec<usize>,
+    avg_return_length: f64,
+
+    /// Our halite plus everything spent so far, as of the last frame.
+    #[serde(skip)]
+    last_halite: i64,
+    #[serde(skip)]
+    total_spent: usize,
+    #[serde(skip)]
+    prev_ships: HashMap<ShipId, ShipSnapshot>,
+}
+
+impl Statistics {
+    pub fn new(game: &Game) -> Self {
+        Statistics {
+            turns: Vec::with_capacity(game.constants.max_turns),
+            ships: BTreeMap::new(),
+            collection_rate: Vec::with_capacity(game.constants.max_turns),
+            halite_percentiles: vec![0; 101],
+            avg_return_length: 0.0,
+            last_halite: game.constants.initial_halite as i64,
+            total_spent: 0,
+            prev_ships: HashMap::new(),
+        }
+    }
+
+    /// `total_spent` must include last turn's expenses, which this frame's halite already
+    /// reflects.
+    pub fn update_frame(&mut self, game: &Game, events: &[GameEvent], total_spent: usize) {
+        let me = &game.players[game.my_id.0];
+
+        let mut map_halite: Vec<_> = game.map.iter().map(|cell| cell.halite).collect();
+        map_halite.sort_unstable();
+        let n = map_halite.len() - 1;
+        for i in 0..=100 {
+            self.halite_percentiles[i] = map_halite[(n * i) / 100];
+        }
+        Log::log(&format!("Halite quartiles: {:?}", self.halite_percentiles));
+
+        // signed, because halite lost to an expense the treasury did not confirm is negative
+        let halite = (me.halite + total_spent) as i64;
+        let delta = (halite - self.last_halite) as f64;
+        let nship = me.ship_ids.len().max(1) as f64;
+        self.collection_rate.push(delta / nship);
+        self.last_halite = halite;
+        self.total_spent = total_spent;
+
+        let mut record = TurnRecord {
+            turn: game.turn_number,
+            halite: me.halite,
+            n_ships: me.ship_ids.len(),
+            halite_left: map_halite.iter().sum(),
+            ..TurnRecord::default()
+        };
+
+        let is_my_structure = |pos: &Position| match game.map.at_position(pos).structure {
+            Structure::Shipyard(pid) => pid == game.my_id,
+            Structure::Dropoff(did) => game.dropoffs[&did].owner == game.my_id,
+            Structure::None => false,
+        };
+
+        for (&id, prev) in &self.prev_ships {
+            let ship_record = self.ships.entry(id.0).or_default();
+
+            let ship = match game.ships.get(&id) {
+                Some(ship) => ship,
+                None => continue,
+            };
+
+            if ship.position == prev.pos {
+                let mined = ship.halite.saturating_sub(prev.cargo);
+                ship_record.mined += mined;
+                record.mined += mined;
+                if mined == 0 {
+                    ship_record.idle_turns += 1;
+                    record.idle_ships += 1;
+                }
+            } else {
+                let burned = prev.cell_halite / game.constants.move_cost_ratio;
+                ship_record.burned += burned;
+                record.burned += burned;
+                if is_my_structure(&ship.position) {
+                    let delivered = prev.cargo.saturating_sub(burned);
+                    ship_record.delivered += delivered;
+                    record.delivered += delivered;
+                }
+            }
+        }
+
+        for event in events {
+            match *event {
+                GameEvent::ShipDestroyed {
+                    id, owner, cargo, ..
+                } if owner == game.my_id => {
+                    let ship_record = self.ships.entry(id.0).or_default();
+                    ship_record.removed = Some(game.turn_number);
+                    ship_record.lost_in_collision += cargo;
+                    record.lost_in_collisions += cargo;
+                    record.ships_lost += 1;
+                }
+                GameEvent::DropoffCreated {
+                    builder: Some(id),
+                    owner,
+                    ..
+                } if owner == game.my_id => {
+                    let ship_record = self.ships.entry(id.0).or_default();
+                    ship_record.removed = Some(game.turn_number);
+                    ship_record.became_dropoff = true;
+                }
+                _ => {}
+            }
+        }
+
+        self.prev_ships.clear();
+        for id in &me.ship_ids {
+            let ship = &game.ships[id];
+            self.ships.entry(id.0).or_insert_with(|| ShipRecord {
+                spawned: game.turn_number,
+                ..ShipRecord::default()
+            });
+            self.prev_ships.insert(
+                *id,
+                ShipSnapshot {
+                    pos: ship.position,
+                    cargo: ship.halite,
+                    cell_halite: game.map.at_position(&ship.position).halite,
+                },
+            );
+        }
+
+        self.turns.push(record);
+    }
+
+    /// A ship finished delivering its cargo after `turns_taken` turns.
+    pub fn record_return(&mut self, id: ShipId, turns_taken: usize, update_rate: f64) {
+        self.avg_return_length =
+            self.avg_return_length * update_rate + turns_taken as f64 * (1.0 - update_rate);
+        Log::log(&format!(
+            "Average return length: {}",
+            self.avg_return_length
+        ));
+
+        if let Some(ship) = self.ships.get_mut(&id.0) {
+            ship.return_trips.push(turns_taken);
+        }
+    }
+
+    /// Adaptive average of the turns ships take to return their cargo.
+    pub fn avg_return_length(&self) -> f64 {
+        self.avg_return_length
+    }
+
+    /// A new dropoff shortens the way home, so the old average no longer applies.
+    pub fn reset_return_length(&mut self) {
+        self.avg_return_length = 0.0;
+    }
+
+    /// Halite one of our ships has delivered so far.
+    pub fn delivered(&self, id: ShipId) -> usize {
+        self.ships
+            .get(&id.0)
+            .map(|ship| ship.delivered)
+            .unwrap_or(0)
+    }
+
+    /// Halite returned per ship in each turn so far.
+    pub fn collection_rate(&self) -> &[f64] {
+        &self.collection_rate
+    }
+
+    /// Write a machine readable summary of the game.
+    pub fn write_summary(&self, game: &Game, filename: &str) -> std::io::Result<()> {
+        let sum = |f: fn(&TurnRecord) -> usize| self.turns.iter().map(f).sum();
+
+        let summary = Summary {
+            player: game.my_id.0,
+            turns_played: game.turn_number,
+            final_halite: game.players[game.my_id.0].halite,
+            totals: Totals {
+                mined: sum(|t| t.mined),
+                delivered: sum(|t| t.delivered),
+                burned: sum(|t| t.burned),
+                lost_in_collisions: sum(|t| t.lost_in_collisions),
+                idle_turns: sum(|t| t.idle_ships),
+                ships_built: self.ships.len(),
+                ships_lost: sum(|t| t.ships_lost),
+                dropoffs_built: self.ships.values().filter(|s| s.became_dropoff).count(),
+                spent: self.total_spent,
+            },
+            collection_rate: &self.collection_rate,
+            turns: &self.turns,
+            ships: &self.ships,
+        };
+
+        let mut file = File::create(filename)?;
+        file.write_all(serde_json::to_string_pretty(&summary)?.as_bytes())
+    }
+}
@@ -0,0 +1,419 @@
+use baselines::Baseline;
+use config::{Config, ConfigError, GameMode};
+use engine::{play_game, Bot, GameSettings, LocalBot, PlayerResult, ProcessBot};
+use neural::Policy;
+use rating::{elo_from_score, Sprt, SprtDecision, SprtStatus};
+use rng::Rng;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::channel;
+use std::thread;
+use BotOptions;
+
+/// A bot taking part in local games.
+#[derive(Debug, Clone)]
+pub struct BotSpec {
+    pub label: String,
+    kind: BotKind,
+}
+
+#[derive(Debug, Clone)]
+enum BotKind {
+    /// This bot, run in a thread of the current process.
+    Local(BotOptions),
+    /// A shell command starting a bot executable.
+    Command(String),
+}
+
+impl BotSpec {
+    pub fn local(label: &str, opts: BotOptions) -> Self {
+        BotSpec {
+            label: label.to_string(),
+            kind: BotKind::Local(opts),
+        }
+    }
+
+    /// `self` is this bot with `opts`, `self:FILE` is this bot with config file `FILE`,
+    /// `baseline:NAME` is one of the baseline strategies, and anything else is a shell command.
+    /// A `NAME=` prefix sets the label, which otherwise is the spec itself, the baseline's name
+    /// or, for `self:FILE`, the file name without `.cfg.json`.
+    pub fn parse(spec: &str, opts: &BotOptions) -> Result<Self, String> {
+        let (name, spec) = match spec.find('=') {
+            Some(i) if is_name(&spec[..i]) => (Some(&spec[..i]), &spec[i + 1..]),
+            _ => (None, spec),
+        };
+
+        let mut bot = if let Some(file) = spec.strip_prefix("self:") {
+            let opts = BotOptions {
+                config_file: Some(file.to_string()),
+                ..opts.clone()
+            };
+            let stem = Path::new(file)
+                .file_name()
+                .and_then(|f| f.to_str())
+                .unwrap_or(file);
+            let stem = stem.trim_end_matches(".json").trim_end_matches(".cfg");
+            BotSpec::local(stem, opts)
+        } else if let Some(name) = spec.strip_prefix("baseline:") {
+            let baseline = Baseline::from_name(name).ok_or_else(|| {
+                let names: Vec<_> = Baseline::ALL.iter().map(|b| b.name()).collect();
+                format!(
+                    "unknown baseline '{}', try one of {}",
+                    name,
+                    names.join(", ")
+                )
+            })?;
+            let opts = BotOptions {
+                baseline: Some(baseline),
+                ..opts.clone()
+            };
+            BotSpec::local(name, opts)
+        } else if spec == "self" {
+            BotSpec::local(spec, opts.clone())
+        } else {
+            BotSpec {
+                label: spec.to_string(),
+                kind: BotKind::Command(spec.to_string()),
+            }
+        };
+
+        if let Some(name) = name {
+            bot.label = name.to_string();
+        }
+        Ok(bot)
+    }
+
+    /// Load the config a local bot would use in games of this mode, to report mistakes before
+    /// any game is played. Returns the config keys the bot would ignore.
+    pub fn check_config(&self, mode: &GameMode) -> Result<Vec<String>, ConfigError> {
+        match self.kind {
+            BotKind::Local(BotOptions {
+                baseline: Some(_), ..
+            }) => Ok(vec![]),
+            BotKind::Local(ref opts) => {
+                let file = opts.config_file.as_ref().map(String::as_ref);
+                let cfg = Config::load(file, mode, &opts.overrides)?;
+                // a broken network would only show up in the bot's log
+                if let Err(e) = Policy::load(&cfg.ships.policy) {
+                    return Err(ConfigError::Invalid(vec![e.to_string()]));
+                }
+                Config::ignored_keys(file, &opts.overrides)
+            }
+            BotKind::Command(_) => Ok(vec![]),
+        }
+    }
+
+    /// Start a new instance for one game.
+    pub fn start(&self) -> io::Result<Box<dyn Bot>> {
+        Ok(match self.kind {
+            BotKind::Local(ref opts) => Box::new(LocalBot::spawn(opts.clone())),
+            BotKind::Command(ref cmd) => Box::new(ProcessBot::spawn(cmd)?),
+        })
+    }
+}
+
+/// Bot labels are single words, so commands with `=` in their arguments are not mistaken for
+/// labels.
+fn is_name(s: &str) -> bool {
+    !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+}
+
+/// Plays a series of games between bots on the built-in engine.
+///
+/// The statistics are from the point of view of the first bot. It takes one seat in every
+/// game, and the other bots fill the remaining seats in turn. Seats and map seeds are drawn
+/// from `seed`, so a tournament can be repeated exactly. With `sprt` set, the tournament
+/// stops as soon as the test reaches a decision.
+pub struct Tournament {
+    pub bots: Vec<BotSpec>,
+    pub games: usize,
+    pub size: usize,
+    pub players: usize,
+    pub seed: u64,
+    pub threads: usize,
+    pub sprt: Option<Sprt>,
+}
+
+/// One game of a tournament.
+#[derive(Debug, Clone, Serialize)]
+pub struct Outcome {
+    /// Number of the game in the schedule.
+    pub game: usize,
+    pub seed: u64,
+    /// Index into `Tournament::bots` of the bot in each seat.
+    pub seats: Vec<usize>,
+    pub players: Vec<PlayerResult>,
+}
+
+impl Outcome {
+    /// Seat of the first bot.
+    fn hero(&self) -> usize {
+        self.seats.iter().position(|&b| b == 0).unwrap()
+    }
+
+    /// Fraction of opponents the first bot finished ahead of.
+    fn score(&self) -> f64 {
+        let players = self.players.len();
+        (players - self.players[self.hero()].rank) as f64 / (players - 1) as f64
+    }
+
+    /// Halite of the first bot minus the mean halite of its opponents.
+    fn score_difference(&self) -> f64 {
+        let hero = self.hero();
+        let others: Vec<f64> = (0..self.players.len())
+            .filter(|&seat| seat != hero)
+            .map(|seat| self.players[seat].halite as f64)
+            .collect();
+        self.players[hero].halite as f64 - others.iter().sum::<f64>() / others.len() as f64
+    }
+}
+
+/// Sample statistics with a 95% confidence interval of the mean.
+#[derive(Debug, Clone, Serialize)]
+pub struct Summary {
+    pub mean: f64,
+    pub std_dev: f64,
+    pub median: f64,
+    pub ci95: (f64, f64),
+}
+
+impl Summary {
+    fn of(values: &[f64]) -> Self {
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let var = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0).max(1.0);
+        let mut sorted = values.to_vec();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let median = if sorted.len() % 2 == 1 {
+            sorted[sorted.len() / 2]
+        } else {
+            (sorted[sorted.len() / 2 - 1] + sorted[sorted.len() / 2]) / 2.0
+        };
+        let half_width = 1.96 * var.sqrt() / n.sqrt();
+        Summary {
+            mean,
+            std_dev: var.sqrt(),
+            median,
+            ci95: (mean - half_width, mean + half_width),
+        }
+    }
+}
+
+/// Wilson score interval for a win rate, which behaves well for few games and rates near 0
+/// or 1.
+fn wilson_ci95(wins: usize, games: usize) -> (f64, f64) {
+    let z = 1.96;
+    let n = games as f64;
+    let p = wins as f64 / n;
+    let center = (p + z * z / (2.0 * n)) / (1.0 + z * z / n);
+    let half_width = z / (1.0 + z * z / n) * (p * (1.0 - p) / n + z * z / (4.0 * n * n)).sqrt();
+    (center - half_width, center + half_width)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub bots: Vec<String>,
+    pub size: usize,
+    pub players: usize,
+    pub seed: u64,
+    /// Games played; fewer than scheduled if the SPRT stopped the tournament.
+    pub games: usize,
+    pub wins: usize,
+    pub win_rate: f64,
+    pub win_rate_ci95: (f64, f64),
+    /// Elo difference of the first bot to its opponents, from the fraction of opponents it
+    /// finished ahead of.
+    pub elo: f64,
+    pub elo_ci95: (f64, f64),
+    pub sprt: Option<SprtStatus>,
+    /// How often the first bot finished first, second, ...
+    pub ranks: Vec<usize>,
+    /// Halite of the first bot minus the mean of its opponents.
+    pub score_difference: Summary,
+    /// Games in which a bot stopped responding, per bot.
+    pub crashes: Vec<usize>,
+    pub outcomes: Vec<Outcome>,
+}
+
+impl Report {
+    fn new(tournament: &Tournament, mut outcomes: Vec<Outcome>) -> Self {
+        outcomes.sort_by_key(|o| o.game);
+        let games = outcomes.len();
+
+        let mut ranks = vec![0; tournament.players];
+        let mut crashes = vec![0; tournament.bots.len()];
+        for o in &outcomes {
+            ranks[o.players[o.hero()].rank - 1] += 1;
+            for (seat, p) in o.players.iter().enumerate() {
+                if p.crashed {
+                    crashes[o.seats[seat]] += 1;
+                }
+            }
+        }
+
+        let scores: Vec<f64> = outcomes.iter().map(Outcome::score).collect();
+        let score = Summary::of(&scores);
+        let total_score = scores.iter().sum();
+
+        let diffs: Vec<f64> = outcomes.iter().map(Outcome::score_difference).collect();
+        Report {
+            bots: tournament.bots.iter().map(|b| b.label.clone()).collect(),
+            size: tournament.size,
+            players: tournament.players,
+            seed: tournament.seed,
+            games,
+            wins: ranks[0],
+            win_rate: ranks[0] as f64 / games as f64,
+            win_rate_ci95: wilson_ci95(ranks[0], games),
+            elo: elo_from_score(score.mean),
+            elo_ci95: (elo_from_score(score.ci95.0), elo_from_score(score.ci95.1)),
+            sprt: tournament.sprt.map(|t| t.status(total_score, games)),
+            ranks,
+            score_difference: Summary::of(&diffs),
+            crashes,
+            outcomes,
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} vs {}", self.bots[0], self.bots[1..].join(", "))?;
+        writeln!(
+            f,
+            "{} games, {}x{}, {} players, seed {}",
+            self.games, self.size, self.size, self.players, self.seed
+        )?;
+        writeln!(
+            f,
+            "win rate:   {:.1}% ({} wins), 95% CI {:.1}% .. {:.1}%",
+            100.0 * self.win_rate,
+            self.wins,
+            100.0 * self.win_rate_ci95.0,
+            100.0 * self.win_rate_ci95.1
+        )?;
+        writeln!(
+            f,
+            "elo:        {:+.0}, 95% CI {:+.0} .. {:+.0}",
+            self.elo, self.elo_ci95.0, self.elo_ci95.1
+        )?;
+        if let Some(ref sprt) = self.sprt {
+            writeln!(f, "sprt:       {}", sprt)?;
+        }
+        let ranks: Vec<_> = self.ranks.iter().map(usize::to_string).collect();
+        writeln!(f, "ranks:      {}", ranks.join(" / "))?;
+        let d = &self.score_difference;
+        writeln!(
+            f,
+            "score diff: mean {:.0}, median {:.0}, std {:.0}, 95% CI {:.0} .. {:.0}",
+            d.mean, d.median, d.std_dev, d.ci95.0, d.ci95.1
+        )?;
+        for (bot, &n) in self.bots.iter().zip(&self.crashes) {
+            if n > 0 {
+                writeln!(f, "crashes:    {} in {} games", bot, n)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Tournament {
+    /// Seat assignments and map seeds of all games.
+    fn schedule(&self) -> Vec<(u64, Vec<usize>)> {
+        let mut lineup = vec![0];
+        lineup.extend((1..self.bots.len()).cycle().take(self.players - 1));
+
+        let mut rng = Rng::new(self.seed);
+        (0..self.games)
+            .map(|_| {
+                let mut seats = lineup.clone();
+                rng.shuffle(&mut seats);
+                (rng.next_u64() % 1_000_000_000, seats)
+            })
+            .collect()
+    }
+
+    fn play(&self, game: usize, seed: u64, seats: Vec<usize>) -> io::Result<Outcome> {
+        let mut bots = seats
+            .iter()
+            .map(|&b| self.bots[b].start())
+            .collect::<io::Result<Vec<_>>>()?;
+        let settings = GameSettings {
+            size: self.size,
+            players: self.players,
+            seed,
+        };
+        let result = play_game(settings, &mut bots);
+        Ok(Outcome {
+            game,
+            seed,
+            seats,
+            players: result.players,
+        })
+    }
+
+    /// Play the games on `threads` threads. `progress` sees the results after every game.
+    pub fn run<F: FnMut(&Report)>(&self, mut progress: F) -> io::Result<Report> {
+        if self.bots.len() < 2 || self.bots.len() > self.players {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("need 2 to {} bots, got {}", self.players, self.bots.len()),
+            ));
+        }
+
+        let schedule = self.schedule();
+        let next = AtomicUsize::new(0);
+        let (tx, rx) = channel();
+        let mut outcomes = vec![];
+
+        thread::scope(|scope| {
+            for _ in 0..self.threads.max(1) {
+                let tx = tx.clone();
+                let (next, schedule) = (&next, &schedule);
+                scope.spawn(move || loop {
+                    let i = next.fetch_add(1, Ordering::SeqCst);
+                    if i >= schedule.len() {
+                        return;
+                    }
+                    let (seed, ref seats) = schedule[i];
+                    if tx.send(self.play(i, seed, seats.clone())).is_err() {
+                        return;
+                    }
+                });
+            }
+            drop(tx);
+
+            for outcome in rx {
+                match outcome {
+                    Ok(outcome) => outcomes.push(outcome),
+                    Err(e) => {
+                        // let the workers run out of games
+                        next.store(schedule.len(), Ordering::SeqCst);
+                        return Err(e);
+                    }
+                }
+                let report = Report::new(self, outcomes.clone());
+                progress(&report);
+                if let Some(SprtStatus { decision, .. }) = report.sprt {
+                    if decision != SprtDecision::Continue {
+                        // games still running are not counted, they would bias the test
+                        next.store(schedule.len(), Ordering::SeqCst);
+                        return Ok(());
+                    }
+                }
+            }
+            Ok(())
+        })?;
+
+        Ok(Report::new(self, outcomes))
+    }
+}
+
+/// Number of threads to use by default.
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
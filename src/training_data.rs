@@ -0,0 +1,157 @@
+use hlt::direction::Direction;
+use hlt::position::Position;
+use hlt::ShipId;
+use npy::{NpyError, NpzWriter};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{BufWriter, ErrorKind};
+use std::path::Path;
+use GameState;
+
+/// Half the side of the square windows around a ship.
+const RADIUS: i32 = 3;
+const SIDE: usize = 2 * RADIUS as usize + 1;
+
+/// Moves in the order of the `plan_move` costs.
+const MOVES: [Direction; 5] = [
+    Direction::Still,
+    Direction::North,
+    Direction::South,
+    Direction::East,
+    Direction::West,
+];
+
+/// Observations and decisions of our ships during one game, to train models on.
+///
+/// Every turn adds a row for each ship that planned a move. At the end of the game the rows
+/// are written as an `.npz` archive with one array per column:
+///
+/// - `turn`, `ship`, `cargo`
+/// - `halite`, `pheromone`, `threat` and `return_cost`: windows of `SIDE` x `SIDE` cells
+///   around the ship, indexed `[row][column]` with rows from north to south. `threat` is 0
+///   where no opponent can go, 1 where one could move and 2 where one is.
+/// - `state`: the ship AI state that planned the move, empty for steered ships
+/// - `costs`: the `plan_move` costs of staying and of moving north, south, east and west
+/// - `action`: the move the navigation system assigned, as an index into `costs`
+/// - `delivered`: the halite the ship delivered after this turn, until the end of the game
+pub struct TrainingData {
+    dir: String,
+    states: HashMap<ShipId, &'static str>,
+
+    turn: Vec<i64>,
+    ship: Vec<i64>,
+    cargo: Vec<i64>,
+    halite: Vec<f32>,
+    pheromone: Vec<f32>,
+    threat: Vec<f32>,
+    return_cost: Vec<f32>,
+    state: Vec<&'static str>,
+    costs: Vec<i64>,
+    action: Vec<i64>,
+    /// What the ship had delivered before the row's turn, until `write` subtracts it from the
+    /// ship's total.
+    delivered: Vec<i64>,
+}
+
+impl TrainingData {
+    pub fn new(dir: &str) -> Self {
+        TrainingData {
+            dir: dir.to_string(),
+            states: HashMap::new(),
+            turn: vec![],
+            ship: vec![],
+            cargo: vec![],
+            halite: vec![],
+            pheromone: vec![],
+            threat: vec![],
+            return_cost: vec![],
+            state: vec![],
+            costs: vec![],
+            action: vec![],
+            delivered: vec![],
+        }
+    }
+
+    /// Remember the state that steps a ship. The last one to step in a turn planned the move.
+    pub fn note_state(&mut self, id: ShipId, name: &'static str) {
+        self.states.insert(id, name);
+    }
+
+    /// Add the rows of this turn, once the moves are solved.
+    pub fn record(&mut self, world: &GameState) {
+        for id in world.my_ships() {
+            let (costs, d) = match (world.gns.planned_costs(id), world.gns.assigned_move(id)) {
+                (Some(costs), Some(d)) => (costs, d),
+                _ => continue,
+            };
+            let ship = world.get_ship(id);
+
+            for dy in -RADIUS..=RADIUS {
+                for dx in -RADIUS..=RADIUS {
+                    let p = world.game.map.normalize(&Position {
+                        x: ship.position.x + dx,
+                        y: ship.position.y + dy,
+                    });
+                    let (i, j) = (p.y as usize, p.x as usize);
+                    self.halite
+                        .push(world.game.map.at_position(&p).halite as f32);
+                    self.pheromone.push(world.pheromones[i][j] as f32);
+                    self.threat.push(world.mp.threat_level(p) as f32);
+                    self.return_cost
+                        .push(world.return_cumultive_costs[i][j] as f32);
+                }
+            }
+
+            self.turn.push(world.game.turn_number as i64);
+            self.ship.push(id.0 as i64);
+            self.cargo.push(ship.halite as i64);
+            self.state.push(self.states.get(&id).cloned().unwrap_or(""));
+            self.costs.extend_from_slice(&costs);
+            self.action
+                .push(MOVES.iter().position(|&m| m == d).unwrap_or(0) as i64);
+            self.delivered.push(world.statistics.delivered(id) as i64);
+        }
+        self.states.clear();
+    }
+
+    /// Write the game to a new file in the export directory and return its name.
+    pub fn write(&self, world: &GameState) -> Result<String, NpyError> {
+        let delivered: Vec<i64> = self
+            .ship
+            .iter()
+            .zip(&self.delivered)
+            .map(|(&id, &before)| world.statistics.delivered(ShipId(id as usize)) as i64 - before)
+            .collect();
+
+        let n = self.turn.len();
+        let window = [n, SIDE, SIDE];
+        let mut npz = NpzWriter::default();
+        npz.add_i64("turn", &[n], &self.turn);
+        npz.add_i64("ship", &[n], &self.ship);
+        npz.add_i64("cargo", &[n], &self.cargo);
+        npz.add_f32("halite", &window, &self.halite);
+        npz.add_f32("pheromone", &window, &self.pheromone);
+        npz.add_f32("threat", &window, &self.threat);
+        npz.add_f32("return_cost", &window, &self.return_cost);
+        npz.add_strings("state", &self.state);
+        npz.add_i64("costs", &[n, MOVES.len()], &self.costs);
+        npz.add_i64("action", &[n], &self.action);
+        npz.add_i64("delivered", &[n], &delivered);
+
+        // games played side by side must not overwrite each other's files
+        fs::create_dir_all(&self.dir)?;
+        let mut i = 0;
+        loop {
+            let name = format!("game-{:04}-p{}.npz", i, world.game.my_id.0);
+            let file = Path::new(&self.dir).join(name);
+            match OpenOptions::new().write(true).create_new(true).open(&file) {
+                Ok(f) => {
+                    npz.write(BufWriter::new(f))?;
+                    return Ok(file.to_string_lossy().into_owned());
+                }
+                Err(ref e) if e.kind() == ErrorKind::AlreadyExists => i += 1,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use hlt::game::Game;
+use hlt::log::Log;
+use hlt::map_cell::Structure;
+use hlt::position::Position;
+use hlt::ship::Ship;
+use hlt::ShipId;
+use std::collections::{HashMap, HashSet};
+
+/// Halite set aside for a planned expense that is not issued yet.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize)]
+pub enum Reservation {
+    Dropoff(Position),
+}
+
+/// Something we pay for with a command this turn.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize)]
+pub enum Expense {
+    Spawn,
+    Dropoff(ShipId, Position),
+}
+
+/// Keeps track of our halite.
+///
+/// - *available* halite can be spent freely this turn,
+/// - *reserved* halite is set aside for planned expenses and stays reserved across turns until
+///   it is released or spent, and
+/// - *committed* halite has been spent by commands issued this turn.
+///
+/// Every frame the treasury is reconciled with the halite reported by the server, and last
+/// turn's expenses are only counted as spent once their effect is visible in the game.
+#[derive(Serialize)]
+pub struct Treasury {
+    halite: usize,
+    reserved: HashMap<Reservation, usize>,
+    committed: Vec<(Expense, usize)>,
+    total_spent: usize,
+
+    #[serde(skip)]
+    known_ships: HashSet<ShipId>,
+}
+
+impl Treasury {
+    pub fn new() -> Self {
+        Treasury {
+            halite: 0,
+            reserved: HashMap::new(),
+            committed: vec![],
+            total_spent: 0,
+            known_ships: HashSet::new(),
+        }
+    }
+
+    pub fn reconcile(&mut self, game: &Game) {
+        let me = &game.players[game.my_id.0];
+        self.halite = me.halite;
+
+        // last turn's commands show up in this frame, so check them against the ships we knew
+        // before it
+        let new_ship_at_shipyard = me
+            .ship_ids
+            .iter()
+            .filter(|id| !self.known_ships.contains(id))
+            .any(|id| game.ships[id].position == me.shipyard.position);
+
+        for (expense, cost) in self.committed.drain(..) {
+            let confirmed = match expense {
+                Expense::Spawn => new_ship_at_shipyard,
+                Expense::Dropoff(id, pos) => {
+                    !game.ships.contains_key(&id)
+                        && match game.map.at_position(&pos).structure {
+                            Structure::Dropoff(did) => game.dropoffs[&did].owner == game.my_id,
+                            _ => false,
+                        }
+                }
+            };
+
+            if confirmed {
+                self.total_spent += cost;
+            } else {
+                Log::log(&format!(
+                    "Treasury: {:?} for {} halite did not happen",
+                    expense, cost
+                ));
+            }
+        }
+
+        self.known_ships = me.ship_ids.iter().cloned().collect();
+
+        Log::log(&format!(
+            "Treasury: {} halite, {} reserved, {} available",
+            self.halite,
+            self.reserved(),
+            self.available()
+        ));
+    }
+
+    pub fn reserved(&self) -> usize {
+        self.reserved.values().sum()
+    }
+
+    pub fn committed(&self) -> usize {
+        self.committed.iter().map(|&(_, cost)| cost).sum()
+    }
+
+    pub fn available(&self) -> usize {
+        self.halite
+            .saturating_sub(self.reserved())
+            .saturating_sub(self.committed())
+    }
+
+    /// Total halite spent on confirmed spawns and dropoffs so far.
+    pub fn total_spent(&self) -> usize {
+        self.total_spent
+    }
+
+    /// Set aside (or update the amount of) halite for a planned expense.
+    pub fn reserve(&mut self, r: Reservation, amount: usize) {
+        self.reserved.insert(r, amount);
+    }
+
+    pub fn release(&mut self, r: Reservation) {
+        self.reserved.remove(&r);
+    }
+
+    pub fn reserved_for(&self, r: Reservation) -> usize {
+        self.reserved.get(&r).cloned().unwrap_or(0)
+    }
+
+    /// True if the expense can be paid from available halite plus its own reservation.
+    pub fn can_afford(&self, expense: Expense, cost: usize) -> bool {
+        cost <= self.available() + self.reservation_for(expense).map(|(_, a)| a).unwrap_or(0)
+    }
+
+    /// Commit halite for an expense, consuming a matching reservation.
+    /// Returns false and spends nothing if we cannot afford it.
+    pub fn try_spend(&mut self, expense: Expense, cost: usize) -> bool {
+        if !self.can_afford(expense, cost) {
+            return false;
+        }
+
+        if let Some((r, _)) = self.reservation_for(expense) {
+            self.reserved.remove(&r);
+        }
+
+        self.committed.push((expense, cost));
+        true
+    }
+
+    fn reservation_for(&self, expense: Expense) -> Option<(Reservation, usize)> {
+        match expense {
+            Expense::Spawn => None,
+            Expense::Dropoff(_, pos) => {
+                let r = Reservation::Dropoff(pos);
+                self.reserved.get(&r).map(|&amount| (r, amount))
+            }
+        }
+    }
+
+    /// The actual cost of turning a ship into a dropoff at its current position. The ship's
+    /// cargo and the halite on the cell go toward the construction.
+    pub fn dropoff_cost(game: &Game, ship: &Ship) -> usize {
+        Treasury::dropoff_cost_at(game, ship.position, ship.halite)
+    }
+
+    pub fn dropoff_cost_at(game: &Game, pos: Position, cargo: usize) -> usize {
+        game.constants
+            .dropoff_cost
+            .saturating_sub(cargo)
+            .saturating_sub(game.map.at_position(&pos).halite)
+    }
+}
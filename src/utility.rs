@@ -0,0 +1,365 @@
+use config::{Curve, Response, Utility};
+use hlt::direction::Direction;
+use hlt::log::Log;
+use hlt::map_cell::Structure;
+use hlt::position::Position;
+use hlt::ShipId;
+use GameState;
+
+/// Moves in the order of `Direction::get_all_options`.
+const N_MOVES: usize = 5;
+
+fn slot(d: Direction) -> usize {
+    match d {
+        Direction::West => 0,
+        Direction::East => 1,
+        Direction::North => 2,
+        Direction::South => 3,
+        Direction::Still => 4,
+    }
+}
+
+impl Curve {
+    pub fn eval(&self, x: f64) -> f64 {
+        match *self {
+            Curve::Linear { slope, intercept } => slope * x + intercept,
+            Curve::Ramp { threshold, slope } => slope * (x - threshold).max(0.0),
+            Curve::Step {
+                threshold,
+                below,
+                above,
+            } => {
+                if x > threshold {
+                    above
+                } else {
+                    below
+                }
+            }
+            Curve::Logistic {
+                midpoint,
+                steepness,
+            } => 1.0 / (1.0 + (-steepness * (x - midpoint)).exp()),
+        }
+    }
+}
+
+/// What a collecting ship knows about its surroundings when it decides where to move.
+pub struct MoveContext {
+    /// Halite we would extract by staying, inspiration included.
+    pub halite_here: usize,
+    pub on_structure: bool,
+    /// A friend spotted prey nearby and calls for help.
+    pub called_to_hunt: bool,
+    /// Pheromone level at each move's target.
+    pub pheromones: [f64; N_MOVES],
+    /// Return cost from each move's target.
+    pub return_costs: [i32; N_MOVES],
+    /// Targets that may be occupied by an opponent next turn.
+    pub dangerous: [bool; N_MOVES],
+    /// Cargo advantage, scaled by our aggressiveness, of opponents we could hunt down.
+    pub prey: [Option<f64>; N_MOVES],
+}
+
+impl MoveContext {
+    pub fn observe(id: ShipId, world: &GameState) -> Self {
+        let pos = world.get_ship(id).position;
+        let cargo = world.get_ship(id).halite as i32;
+        let careful = cargo <= world.config.ships.carefulness_limit;
+
+        let mut ctx = MoveContext {
+            halite_here: world.halite_gain(&pos) * world.game.constants.extract_ratio,
+            on_structure: world.game.map.at_position(&pos).structure != Structure::None,
+            called_to_hunt: world.get_pheromone(pos) >= world.config.pheromones.prey_attraction,
+            pheromones: [0.0; N_MOVES],
+            return_costs: [0; N_MOVES],
+            dangerous: [false; N_MOVES],
+            prey: [None; N_MOVES],
+        };
+
+        let [c0, cn, cs, ce, cw] = world.get_return_dir_costs(pos);
+        ctx.return_costs = [cw, ce, cn, cs, c0];
+
+        for d in Direction::get_all_options() {
+            let p = pos.directional_offset(d);
+            let i = slot(d);
+            ctx.pheromones[i] = world.get_pheromone(p);
+            ctx.dangerous[i] = if careful {
+                world.mp.is_occupied(p)
+            } else {
+                world.mp.is_reachable(p)
+            };
+            if d != Direction::Still {
+                ctx.prey[i] = prey_value(pos, cargo, p, world);
+            }
+        }
+
+        ctx
+    }
+
+    pub fn has_prey(&self) -> bool {
+        self.prey.iter().any(Option::is_some)
+    }
+
+    /// Nothing to harvest here or next door.
+    fn is_idle(&self) -> bool {
+        self.halite_here < 1
+            && Direction::get_all_cardinals()
+                .into_iter()
+                .all(|d| self.pheromones[slot(d)] < 1.0)
+    }
+}
+
+/// Value of attacking an opponent ship at `p`, if our friends nearby can pick up the spilled
+/// cargo before the opponents can.
+fn prey_value(pos: Position, cargo: i32, p: Position, world: &GameState) -> Option<f64> {
+    let ship = world.get_ship_at(p)?;
+    if ship.owner == world.game.my_id {
+        return None;
+    }
+
+    let other_cargo = ship.halite as i32;
+    if other_cargo <= cargo {
+        return None;
+    }
+
+    let r = world
+        .find_nearest_oponent(p, true)
+        .map(|id| world.get_ship(id).position)
+        .map(|sp| world.game.map.calculate_distance(&p, &sp))
+        .unwrap_or(world.config.ships.prey_escort_radius);
+
+    Log::log(&format!(
+        "potential prey at {:?} with nearest opponent {} steps away...",
+        p, r
+    ));
+
+    let free_cargo = world
+        .my_ships()
+        .map(|id| world.get_ship(id))
+        .filter(|ship| ship.position != pos)
+        .filter(|ship| world.game.map.calculate_distance(&p, &ship.position) <= r)
+        .inspect(|ship| Log::log(&format!("   ... and friendly ship at {:?}", ship.position)))
+        .map(|ship| ship.capacity() as i32)
+        .sum::<i32>();
+
+    if free_cargo <= cargo {
+        return None;
+    }
+
+    Some(world.config.ships.aggressiveness * f64::from(other_cargo - cargo))
+}
+
+/// A named aspect of a move. It maps the situation to a raw input, which the configured
+/// response curve and weight turn into a utility.
+pub trait Consideration {
+    fn name(&self) -> &'static str;
+
+    /// Input for a move in direction `d`, or `None` if the consideration does not apply.
+    fn input(&self, ctx: &MoveContext, d: Direction) -> Option<f64>;
+}
+
+/// Climb the pheromone gradient.
+struct Pheromone;
+
+impl Consideration for Pheromone {
+    fn name(&self) -> &'static str {
+        "pheromone"
+    }
+
+    fn input(&self, ctx: &MoveContext, d: Direction) -> Option<f64> {
+        Some(ctx.pheromones[slot(d)])
+    }
+}
+
+/// Stay where there is more halite than the pheromone promises.
+struct LocalHalite;
+
+impl Consideration for LocalHalite {
+    fn name(&self) -> &'static str {
+        "local_halite"
+    }
+
+    fn input(&self, ctx: &MoveContext, d: Direction) -> Option<f64> {
+        if d != Direction::Still {
+            return None;
+        }
+        Some(ctx.halite_here as f64 - ctx.pheromones[slot(d)])
+    }
+}
+
+/// Harvest rich cells right away, unless we are called to hunt.
+struct GreedyHarvest;
+
+impl Consideration for GreedyHarvest {
+    fn name(&self) -> &'static str {
+        "greedy_harvest"
+    }
+
+    fn input(&self, ctx: &MoveContext, d: Direction) -> Option<f64> {
+        if d != Direction::Still || ctx.called_to_hunt {
+            return None;
+        }
+        Some(ctx.halite_here as f64)
+    }
+}
+
+/// Do not block our own structures.
+struct StructureAvoidance;
+
+impl Consideration for StructureAvoidance {
+    fn name(&self) -> &'static str {
+        "structure"
+    }
+
+    fn input(&self, ctx: &MoveContext, d: Direction) -> Option<f64> {
+        if d == Direction::Still && ctx.on_structure {
+            Some(1.0)
+        } else {
+            None
+        }
+    }
+}
+
+/// Do not loiter where there is nothing to harvest.
+struct Loitering;
+
+impl Consideration for Loitering {
+    fn name(&self) -> &'static str {
+        "loitering"
+    }
+
+    fn input(&self, ctx: &MoveContext, d: Direction) -> Option<f64> {
+        if d == Direction::Still && ctx.is_idle() {
+            Some(1.0)
+        } else {
+            None
+        }
+    }
+}
+
+/// When idle, drift away from our structures (positive input) to find new halite.
+struct ReturnCostDrift;
+
+impl Consideration for ReturnCostDrift {
+    fn name(&self) -> &'static str {
+        "return_drift"
+    }
+
+    fn input(&self, ctx: &MoveContext, d: Direction) -> Option<f64> {
+        if d == Direction::Still || !ctx.is_idle() {
+            return None;
+        }
+        let c0 = ctx.return_costs[slot(Direction::Still)];
+        Some(f64::from(ctx.return_costs[slot(d)] - c0))
+    }
+}
+
+/// Avoid positions opponents may move to, unless we hunt the ship there.
+struct Threat;
+
+impl Consideration for Threat {
+    fn name(&self) -> &'static str {
+        "threat"
+    }
+
+    fn input(&self, ctx: &MoveContext, d: Direction) -> Option<f64> {
+        let i = slot(d);
+        if ctx.dangerous[i] && ctx.prey[i].is_none() {
+            Some(1.0)
+        } else {
+            None
+        }
+    }
+}
+
+/// Ram opponents that carry more than we do.
+struct Prey;
+
+impl Consideration for Prey {
+    fn name(&self) -> &'static str {
+        "prey"
+    }
+
+    fn input(&self, ctx: &MoveContext, d: Direction) -> Option<f64> {
+        ctx.prey[slot(d)]
+    }
+}
+
+fn considerations(cfg: &Utility) -> Vec<(&'static dyn Consideration, &Response)> {
+    vec![
+        (&Pheromone, &cfg.pheromone),
+        (&LocalHalite, &cfg.local_halite),
+        (&GreedyHarvest, &cfg.greedy_harvest),
+        (&StructureAvoidance, &cfg.structure),
+        (&Loitering, &cfg.loitering),
+        (&ReturnCostDrift, &cfg.return_drift),
+        (&Threat, &cfg.threat),
+        (&Prey, &cfg.prey),
+    ]
+}
+
+/// Utilities of all moves, broken down by consideration.
+pub struct MoveScores {
+    rows: Vec<(&'static str, [Option<f64>; N_MOVES])>,
+    totals: [f64; N_MOVES],
+}
+
+impl MoveScores {
+    pub fn evaluate(ctx: &MoveContext, cfg: &Utility) -> Self {
+        let mut rows = vec![];
+        let mut totals = [0.0; N_MOVES];
+        for (consideration, response) in considerations(cfg) {
+            let mut row = [None; N_MOVES];
+            for d in Direction::get_all_options() {
+                let i = slot(d);
+                if let Some(x) = consideration.input(ctx, d) {
+                    let u = response.weight * response.curve.eval(x);
+                    row[i] = Some(u);
+                    totals[i] += u;
+                }
+            }
+            rows.push((consideration.name(), row));
+        }
+        MoveScores { rows, totals }
+    }
+
+    /// Movement costs for the navigation system, in the order West, East, North, South, Still.
+    pub fn costs(&self) -> [i32; N_MOVES] {
+        let limit = f64::from(i32::MAX - 10);
+        let mut costs = [0; N_MOVES];
+        for (c, &u) in costs.iter_mut().zip(&self.totals) {
+            *c = (-u * 100.0).clamp(-limit, limit) as i32;
+        }
+        costs
+    }
+
+    /// A table of all contributions to the utilities.
+    pub fn explain(&self, id: ShipId) -> String {
+        let fmt_row = |name: &str, values: [Option<f64>; N_MOVES]| {
+            let cells: Vec<_> = values
+                .iter()
+                .map(|v| match *v {
+                    Some(u) => format!("{:>12.1}", u),
+                    None => format!("{:>12}", "-"),
+                })
+                .collect();
+            format!("\n    {:<14}{}", name, cells.concat())
+        };
+
+        let mut out = format!(
+            "{:?} move utilities:\n    {:<14}{:>12}{:>12}{:>12}{:>12}{:>12}",
+            id, "", "west", "east", "north", "south", "still"
+        );
+        for &(name, row) in &self.rows {
+            if row.iter().any(Option::is_some) {
+                out += &fmt_row(name, row);
+            }
+        }
+        let mut totals = [None; N_MOVES];
+        for (t, &u) in totals.iter_mut().zip(&self.totals) {
+            *t = Some(u);
+        }
+        out += &fmt_row("total", totals);
+        out
+    }
+}